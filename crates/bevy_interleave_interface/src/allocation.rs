@@ -0,0 +1,283 @@
+use std::ops::Range;
+
+use bevy::render::{
+    render_resource::{BufferInitDescriptor, BufferUsages, COPY_BUFFER_ALIGNMENT},
+    renderer::{RenderDevice, RenderQueue},
+};
+
+use crate::{
+    dirty::PlanarDirtyRanges,
+    paging::{PlanarColumnPage, PlanarPaging, prepare_column_pages},
+    partition::PlanarSharing,
+};
+
+// how the column pages of a planar asset are allocated on the GPU
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlanarAllocation {
+    // one buffer per column page
+    #[default]
+    PerColumn,
+    // one buffer per asset, column pages are bound at offsets aligned to `min_storage_buffer_offset_alignment`
+    // note: avoids fragmenting GPU memory with many small assets, the page metadata and draw indirect buffers stay separate
+    Single,
+}
+
+// byte range of every column page within a single allocation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlanarAllocationLayout {
+    // page-major within each column, see `PlanarPaging::expand`
+    pub pages: Vec<Range<u64>>,
+    pub size: u64,
+}
+
+impl PlanarAllocationLayout {
+    // `len` rows of columns with element sizes `strides`, each page starting at a multiple of `alignment`
+    pub fn new(len: usize, strides: &[usize], paging: &PlanarPaging, alignment: u64) -> Self {
+        Self::from_page_sizes(
+            strides
+                .iter()
+                .flat_map(|stride| page_sizes(len, *stride, *paging)),
+            alignment,
+        )
+    }
+
+    pub fn from_page_sizes(sizes: impl IntoIterator<Item = u64>, alignment: u64) -> Self {
+        let alignment = alignment.max(COPY_BUFFER_ALIGNMENT);
+
+        let mut size = 0u64;
+        let pages = sizes
+            .into_iter()
+            .map(|bytes| {
+                let start = size.next_multiple_of(alignment);
+                size = start + bytes;
+                start..size
+            })
+            .collect();
+
+        Self { pages, size }
+    }
+}
+
+// bound bytes of each page of a column, padded to `COPY_BUFFER_ALIGNMENT`
+pub fn page_sizes(len: usize, stride: usize, paging: PlanarPaging) -> impl Iterator<Item = u64> {
+    (0..paging.pages).map(move |page| {
+        let rows = paging.page_rows(len, page);
+
+        // note: unused pages still need a binding of at least one element
+        let bytes = (rows.len() * stride).max(stride.max(4)) as u64;
+        bytes.next_multiple_of(COPY_BUFFER_ALIGNMENT)
+    })
+}
+
+// a tightly packed column uploaded by `prepare_columns`
+#[derive(Clone, Copy)]
+pub struct PlanarColumnUpload<'a> {
+    // buffer label of `PlanarAllocation::PerColumn`
+    pub label: &'static str,
+    pub bytes: &'a [u8],
+    pub stride: usize,
+    // rows written to the `previous` pages
+    pub rows: &'a [Range<usize>],
+    // `u32` words of `PlanarPaging::pack_bits`, paged by `PlanarPaging::bit_packed`
+    pub bit_packed: bool,
+}
+
+impl PlanarColumnUpload<'_> {
+    pub fn paging(&self, paging: &PlanarPaging) -> PlanarPaging {
+        if self.bit_packed {
+            paging.bit_packed()
+        } else {
+            *paging
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len().checked_div(self.stride).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// row-interleaved bytes and dirty rows of the columns of a shared binding
+pub struct PlanarSharedUpload {
+    pub bytes: Vec<u8>,
+    // rows dirty in any of the columns
+    pub rows: Vec<Range<usize>>,
+}
+
+impl PlanarSharedUpload {
+    pub fn new(columns: &[PlanarColumnUpload]) -> Self {
+        let len = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+        let stride = columns.iter().map(|column| column.stride).sum::<usize>();
+
+        let mut bytes = vec![0; len * stride];
+        for (row, shared) in bytes.chunks_exact_mut(stride).enumerate() {
+            let mut offset = 0;
+            for column in columns {
+                if let Some(element) = column
+                    .bytes
+                    .get(row * column.stride..(row + 1) * column.stride)
+                {
+                    shared[offset..offset + column.stride].copy_from_slice(element);
+                }
+                offset += column.stride;
+            }
+        }
+
+        let mut dirty = PlanarDirtyRanges::default();
+        for rows in columns.iter().flat_map(|column| column.rows) {
+            dirty.mark(0, rows.clone());
+        }
+
+        Self {
+            bytes,
+            rows: dirty.column(0).to_vec(),
+        }
+    }
+}
+
+// uploads every column as `paging.pages` pages, allocated according to `allocation`
+// `previous` pages of an asset with the same length are reused, only the `rows` of each column are written
+// note: the columns of a shared binding are interleaved into one upload and hold the same pages
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_columns(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    label: &'static str,
+    columns: &[PlanarColumnUpload],
+    sharing: &PlanarSharing,
+    paging: &PlanarPaging,
+    allocation: PlanarAllocation,
+    previous: Option<&[&[PlanarColumnPage]]>,
+) -> Vec<Vec<PlanarColumnPage>> {
+    if sharing.is_empty() {
+        return prepare_bindings(
+            render_device,
+            render_queue,
+            label,
+            columns,
+            paging,
+            allocation,
+            previous,
+        );
+    }
+
+    let slots = sharing.slots(columns.len());
+    let shared = slots
+        .iter()
+        .map(|slot| {
+            (slot.len() > 1).then(|| {
+                PlanarSharedUpload::new(
+                    &slot
+                        .iter()
+                        .map(|column| columns[*column])
+                        .collect::<Vec<_>>(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    let uploads = slots
+        .iter()
+        .zip(&shared)
+        .map(|(slot, shared)| match shared {
+            Some(shared) => PlanarColumnUpload {
+                bytes: &shared.bytes,
+                stride: slot.iter().map(|column| columns[*column].stride).sum(),
+                rows: &shared.rows,
+                ..columns[slot[0]]
+            },
+            None => columns[slot[0]],
+        })
+        .collect::<Vec<_>>();
+
+    let bindings = prepare_bindings(
+        render_device,
+        render_queue,
+        label,
+        &uploads,
+        paging,
+        allocation,
+        previous.map(|previous| sharing.bound(previous)).as_deref(),
+    );
+
+    (0..columns.len())
+        .map(|column| bindings[sharing.lane(columns.len(), column).0].clone())
+        .collect()
+}
+
+fn prepare_bindings(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    label: &'static str,
+    columns: &[PlanarColumnUpload],
+    paging: &PlanarPaging,
+    allocation: PlanarAllocation,
+    previous: Option<&[&[PlanarColumnPage]]>,
+) -> Vec<Vec<PlanarColumnPage>> {
+    // note: previous pages are written in place whatever their allocation
+    if allocation == PlanarAllocation::PerColumn || previous.is_some() {
+        return columns
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| {
+                prepare_column_pages(
+                    render_device,
+                    render_queue,
+                    column.label,
+                    column.bytes,
+                    column.stride,
+                    &column.paging(paging),
+                    previous.map(|previous| previous[idx]),
+                    column.rows,
+                )
+            })
+            .collect();
+    }
+
+    let layout = PlanarAllocationLayout::from_page_sizes(
+        columns
+            .iter()
+            .flat_map(|column| page_sizes(column.len(), column.stride, column.paging(paging))),
+        render_device.limits().min_storage_buffer_offset_alignment as u64,
+    );
+
+    let mut contents = vec![0; layout.size as usize];
+    let mut ranges = layout.pages.iter();
+    for column in columns {
+        for page in 0..paging.pages {
+            let range = ranges.next().unwrap();
+            let page_bytes = column
+                .paging(paging)
+                .page_bytes(column.bytes, column.stride, page);
+
+            let start = range.start as usize;
+            contents[start..start + page_bytes.len()].copy_from_slice(page_bytes);
+        }
+    }
+
+    let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some(label),
+        contents: &contents,
+        usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+    });
+
+    let mut ranges = layout.pages.into_iter();
+    columns
+        .iter()
+        .map(|_| {
+            (0..paging.pages)
+                .map(|_| {
+                    let range = ranges.next().unwrap();
+
+                    PlanarColumnPage {
+                        buffer: buffer.clone(),
+                        offset: range.start,
+                        size: range.end - range.start,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,188 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    render::{render_resource::DownlevelFlags, settings::WgpuLimits},
+    shader::ShaderDefVal,
+};
+
+use crate::{
+    PlanarStorage, PlanarTexture, storage::PlanarStoragePlugin, sync::PlanarSyncPlugin,
+    texture::PlanarTexturePlugin,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlanarBackend {
+    Storage,
+    Texture,
+}
+
+impl PlanarBackend {
+    // storage is preferred, textures are the fallback for e.g. webgl2 where storage buffers are unavailable
+    // note: storage layouts are visible to the vertex stage, which needs `DownlevelFlags::VERTEX_STORAGE`
+    pub fn select(limits: &WgpuLimits, downlevel_flags: DownlevelFlags) -> Self {
+        if limits.max_storage_buffers_per_shader_stage > 0
+            && downlevel_flags.contains(DownlevelFlags::VERTEX_STORAGE)
+        {
+            PlanarBackend::Storage
+        } else {
+            PlanarBackend::Texture
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            PlanarBackend::Storage => "STORAGE",
+            PlanarBackend::Texture => "TEXTURE",
+        }
+    }
+}
+
+// note: the backend is selected in `finish`, the device limits are unknown while plugins are built
+// systems and pipelines depending on the selection read `PlanarBackendSelection` once the app is finished
+pub struct PlanarAutoPlugin<R> {
+    // overrides `RenderDevice::limits`, e.g. to exercise the texture fallback on a capable device
+    pub limits: Option<WgpuLimits>,
+    // overrides the adapter's downlevel capabilities
+    pub downlevel_flags: Option<DownlevelFlags>,
+    // added and finished as is when storage is selected
+    pub storage: PlanarStoragePlugin<R>,
+    // added and finished as is when textures are selected
+    pub texture: PlanarTexturePlugin<R>,
+}
+impl<R> Default for PlanarAutoPlugin<R> {
+    fn default() -> Self {
+        Self {
+            limits: None,
+            downlevel_flags: None,
+            storage: PlanarStoragePlugin::default(),
+            texture: PlanarTexturePlugin::default(),
+        }
+    }
+}
+
+impl<R> PlanarAutoPlugin<R> {
+    pub fn with_limits(limits: WgpuLimits) -> Self {
+        Self {
+            limits: Some(limits),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_downlevel_flags(self, downlevel_flags: DownlevelFlags) -> Self {
+        Self {
+            downlevel_flags: Some(downlevel_flags),
+            ..self
+        }
+    }
+
+    // e.g. `PlanarStoragePlugin::with_bind_group(1).with_max_rows(..)`
+    pub fn with_storage(self, storage: PlanarStoragePlugin<R>) -> Self {
+        Self { storage, ..self }
+    }
+
+    pub fn with_texture(self, texture: PlanarTexturePlugin<R>) -> Self {
+        Self { texture, ..self }
+    }
+}
+
+impl<R: 'static> Plugin for PlanarAutoPlugin<R>
+where
+    R: PlanarStorage + PlanarTexture + Default + GetTypeRegistration + Clone + Reflect + TypePath,
+{
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<PlanarSyncPlugin<R>>() {
+            app.add_plugins(PlanarSyncPlugin::<R>::default());
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app(bevy::render::RenderApp) else {
+            return;
+        };
+
+        let limits = self.limits.clone().unwrap_or_else(|| {
+            render_app
+                .world()
+                .resource::<bevy::render::renderer::RenderDevice>()
+                .limits()
+        });
+        let downlevel_flags = self.downlevel_flags.unwrap_or_else(|| {
+            render_app
+                .world()
+                .resource::<bevy::render::renderer::RenderAdapter>()
+                .get_downlevel_capabilities()
+                .flags
+        });
+
+        // note: storage bindings still exceeding the limits once columns are shared fall back to textures
+        let backend = match PlanarBackend::select(&limits, downlevel_flags) {
+            PlanarBackend::Storage if self.storage.layout(&limits).is_err() => {
+                PlanarBackend::Texture
+            }
+            backend => backend,
+        };
+
+        // plugins added during `finish` are built but not finished by the app
+        match backend {
+            PlanarBackend::Storage => {
+                app.add_plugins(self.storage.clone());
+                self.storage.finish(app);
+            }
+            PlanarBackend::Texture => {
+                app.add_plugins(self.texture.clone());
+                self.texture.finish(app);
+            }
+        }
+
+        app.insert_resource(PlanarBackendSelection::<R>::new(backend));
+        app.sub_app_mut(bevy::render::RenderApp)
+            .insert_resource(PlanarBackendSelection::<R>::new(backend));
+    }
+}
+
+#[derive(bevy::prelude::Resource)]
+pub struct PlanarBackendSelection<R> {
+    pub backend: PlanarBackend,
+    pub phantom: PhantomData<fn() -> R>,
+}
+
+impl<R: TypePath> PlanarBackendSelection<R> {
+    pub fn new(backend: PlanarBackend) -> Self {
+        Self {
+            backend,
+            phantom: PhantomData,
+        }
+    }
+
+    // e.g. `PLANAR_MY_STRUCT_STORAGE` or `PLANAR_MY_SCALAR_F32_TEXTURE`
+    // note: bevy has no global shader defs, pipelines push this onto `shader_defs` in `specialize` to branch with `#ifdef`
+    pub fn shader_def(&self) -> ShaderDefVal {
+        let mut name = String::from("PLANAR_");
+        let mut previous = '_';
+
+        for c in R::short_type_path().chars() {
+            if !c.is_ascii_alphanumeric() {
+                if !name.ends_with('_') {
+                    name.push('_');
+                }
+            } else {
+                if c.is_ascii_uppercase()
+                    && (previous.is_ascii_lowercase() || previous.is_ascii_digit())
+                {
+                    name.push('_');
+                }
+                name.push(c.to_ascii_uppercase());
+            }
+            previous = c;
+        }
+
+        if !name.ends_with('_') {
+            name.push('_');
+        }
+        name.push_str(self.backend.suffix());
+
+        name.into()
+    }
+}
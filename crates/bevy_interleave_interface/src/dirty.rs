@@ -0,0 +1,133 @@
+use std::{borrow::Cow, ops::Range};
+
+// row ranges per column that changed since the planar asset was last uploaded
+// an empty set means the whole asset must be uploaded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlanarDirtyRanges {
+    columns: Vec<Vec<Range<usize>>>,
+}
+
+impl PlanarDirtyRanges {
+    pub fn is_empty(&self) -> bool {
+        self.columns.iter().all(|column| column.is_empty())
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+
+    // sorted, non-overlapping dirty row ranges of `column`
+    pub fn column(&self, column: usize) -> &[Range<usize>] {
+        self.columns
+            .get(column)
+            .map(|ranges| ranges.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn mark(&mut self, column: usize, rows: Range<usize>) {
+        if rows.is_empty() {
+            return;
+        }
+
+        if self.columns.len() <= column {
+            self.columns.resize_with(column + 1, Vec::new);
+        }
+
+        let ranges = &mut self.columns[column];
+
+        // merge with every overlapping or adjacent range
+        let first = ranges.partition_point(|range| range.end < rows.start);
+        let last = ranges.partition_point(|range| range.start <= rows.end);
+
+        let merged = if first < last {
+            ranges[first].start.min(rows.start)..ranges[last - 1].end.max(rows.end)
+        } else {
+            rows
+        };
+
+        ranges.splice(first..last, std::iter::once(merged));
+    }
+
+    pub fn mark_columns(&mut self, columns: usize, rows: Range<usize>) {
+        for column in 0..columns {
+            self.mark(column, rows.clone());
+        }
+    }
+
+    // rows dirty in any column, e.g. for interleaved uploads
+    pub fn rows(&self) -> Vec<Range<usize>> {
+        self.union(&(0..self.columns.len()).collect::<Vec<_>>())
+    }
+
+    // rows dirty in any of `columns`, e.g. the fields of a stream
+    pub fn union(&self, columns: &[usize]) -> Vec<Range<usize>> {
+        let mut rows = Self::default();
+        for column in columns {
+            for range in self.column(*column) {
+                rows.mark(0, range.clone());
+            }
+        }
+
+        rows.column(0).to_vec()
+    }
+}
+
+// bytes of a tightly packed column written at `offset` within an existing page
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanarDirtyWrite<'a> {
+    pub offset: usize,
+    pub bytes: Cow<'a, [u8]>,
+}
+
+// byte ranges of `rows` are widened to `COPY_BUFFER_ALIGNMENT` and clamped to `page_size`,
+// zero padding past the end of `bytes`
+pub fn dirty_writes<'a>(
+    bytes: &'a [u8],
+    stride: usize,
+    page_size: usize,
+    rows: &[Range<usize>],
+) -> Vec<PlanarDirtyWrite<'a>> {
+    let alignment = bevy::render::render_resource::COPY_BUFFER_ALIGNMENT as usize;
+
+    rows.iter()
+        .filter_map(|range| {
+            let start = (range.start * stride) / alignment * alignment;
+            let end = (range.end * stride).div_ceil(alignment) * alignment;
+            let end = end.min(page_size);
+
+            if start >= end || start >= bytes.len() {
+                return None;
+            }
+
+            let bytes = if end <= bytes.len() {
+                Cow::Borrowed(&bytes[start..end])
+            } else {
+                let mut padded = bytes[start..].to_vec();
+                padded.resize(end - start, 0);
+                Cow::Owned(padded)
+            };
+
+            Some(PlanarDirtyWrite {
+                offset: start,
+                bytes,
+            })
+        })
+        .collect()
+}
+
+// writes the dirty rows of a tightly packed column into an existing page of the same length
+pub fn write_dirty_ranges(
+    render_queue: &bevy::render::renderer::RenderQueue,
+    page: &crate::paging::PlanarColumnPage,
+    bytes: &[u8],
+    stride: usize,
+    rows: &[Range<usize>],
+) {
+    for write in dirty_writes(bytes, stride, page.size as usize, rows) {
+        render_queue.write_buffer(
+            &page.buffer,
+            page.offset + write.offset as u64,
+            &write.bytes,
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use bevy::render::render_resource::BindGroupLayoutEntry;
+
+// e.g. `material.color` for the `color` column of a `#[planar(flatten)]` field `material`
+// note: built once per type, the names are leaked
+pub fn dotted_names(prefix: &str, names: &[&str]) -> Vec<&'static str> {
+    names
+        .iter()
+        .map(|name| &*format!("{prefix}.{name}").leak())
+        .collect()
+}
+
+// the entries of a flattened field, numbered after the last binding of `entries`
+pub fn append_layout_entries(
+    entries: &mut Vec<BindGroupLayoutEntry>,
+    nested: Vec<BindGroupLayoutEntry>,
+) {
+    let first = entries
+        .iter()
+        .map(|entry| entry.binding + 1)
+        .max()
+        .unwrap_or(0);
+
+    entries.extend(nested.into_iter().map(|mut entry| {
+        entry.binding += first;
+        entry
+    }));
+}
+
+// e.g. the stream structs of two fields flattening the same struct are declared once
+pub fn wgsl_column_types(types: &[String]) -> String {
+    let mut declared = String::new();
+    for types in types {
+        if !declared.contains(types.as_str()) {
+            declared.push_str(types);
+        }
+    }
+
+    declared
+}
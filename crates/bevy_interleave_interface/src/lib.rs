@@ -1,5 +1,16 @@
+pub mod allocation;
+pub mod auto;
+pub mod dirty;
+pub mod flatten;
+pub mod list;
+pub mod paging;
+pub mod partition;
+pub mod std430;
 pub mod storage;
-// pub mod texture;
+pub mod sync;
+pub mod texture;
+pub mod validation;
+pub mod validity;
 
 pub trait PlanarHandle<T>
 where
@@ -15,19 +26,30 @@ where
     fn handle(&self) -> &bevy::asset::Handle<T>;
 }
 
-// TODO: migrate to PlanarSync
+// note: implemented by the `Planar` derive, GPU backends extend it with their own render asset
 pub trait PlanarSync
 where
-    Self: Default,
     Self: Send,
     Self: Sync,
-    Self: bevy::reflect::Reflect,
     Self: 'static,
 {
     type PackedType; // Self
     type PlanarType: Planar<PackedType = Self::PackedType>;
     type PlanarTypeHandle: PlanarHandle<Self::PlanarType>;
-    type GpuPlanarType: GpuPlanar<PackedType = Self::PackedType, PlanarType = Self::PlanarType>;
+}
+
+pub trait PlanarStorage
+where
+    Self: PlanarSync,
+{
+    type GpuStorageType: GpuPlanarStorage<PackedType = Self::PackedType, PlanarType = Self::PlanarType>;
+}
+
+pub trait PlanarTexture
+where
+    Self: PlanarSync,
+{
+    type GpuTextureType: GpuPlanarTexture<PackedType = Self::PackedType, PlanarType = Self::PlanarType>;
 }
 
 pub trait GpuPlanar
@@ -53,16 +75,111 @@ where
 {
     fn draw_indirect_buffer(&self) -> &bevy::render::render_resource::Buffer;
 
-    fn bind_group(
+    // `previous` buffers of an asset with the same length are reused, only dirty rows are written
+    // note: `#[planar(flatten)]` fields are uploaded with the paging and allocation of their parent, their columns are never shared
+    fn prepare_storage(
+        source: &Self::PlanarType,
+        render_device: &bevy::render::renderer::RenderDevice,
+        render_queue: &bevy::render::renderer::RenderQueue,
+        sharing: &partition::PlanarSharing,
+        paging: &paging::PlanarPaging,
+        allocation: allocation::PlanarAllocation,
+        previous: Option<&Self>,
+    ) -> Self;
+
+    // one slice of pages per `bind_group_layout_entries` entry
+    fn column_pages(&self) -> Vec<&[paging::PlanarColumnPage]>;
+
+    // note: one entry per column, e.g. per field or a single interleaved column, see `PlanarPaging::expand`
+    // `read_only` applies to fields without a `#[storage(read_only)]` or `#[storage(read_write)]` attribute
+    fn bind_group_layout_entries(
+        read_only: bool,
+    ) -> Vec<bevy::render::render_resource::BindGroupLayoutEntry>;
+
+    // reported by shader validation, one per `bind_group_layout_entries` entry
+    fn binding_names() -> &'static [&'static str];
+
+    // one bind group per `partition` group, `layouts` as returned by `bind_group_layouts`
+    fn bind_groups(
         &self,
         render_device: &bevy::render::renderer::RenderDevice,
-        layout: &bevy::render::render_resource::BindGroupLayout,
-    ) -> bevy::render::render_resource::BindGroup;
+        layouts: &[bevy::render::render_resource::BindGroupLayout],
+        partition: &partition::PlanarPartition,
+    ) -> Vec<bevy::render::render_resource::BindGroup>;
 
-    fn bind_group_layout(
+    // `partition` spans every column page, the page metadata uniform follows the last group's storage bindings
+    fn bind_group_layouts(
         render_device: &bevy::render::renderer::RenderDevice,
         read_only: bool,
-    ) -> bevy::render::render_resource::BindGroupLayout;
+        partition: &partition::PlanarPartition,
+        paging: &paging::PlanarPaging,
+    ) -> Vec<bevy::render::render_resource::BindGroupLayout>;
+
+    // bindings and loaders of each `bind_group_layout_entries` entry, type parameters substituted
+    // note: a struct flattening this one declares them under its own names, see `PlanarWgslColumn::renamed`
+    fn wgsl_columns() -> Vec<paging::PlanarWgslColumn>;
+
+    // structs declared for `wgsl_columns`, e.g. `MyStructStream0`
+    fn wgsl_column_types() -> String;
+
+    // packed struct, `array<T>` page bindings placed by `partition`
+    // note: the columns of `#[planar(flatten)]` fields are only read through their loaders, page helpers and a `get_<packed>(index)` accessor
+    fn wgsl_partitioned_source(
+        partition: &partition::PlanarPartition,
+        paging: &paging::PlanarPaging,
+        read_only: bool,
+    ) -> String;
+
+    // e.g. `bevy_interleave::my_struct::storage` or `bevy_interleave::my_scalar_f32::storage` for `MyScalar<f32>`
+    fn wgsl_import_path() -> String;
+
+    // device features of the substituted type parameters, e.g. `Features::SHADER_F16` for `MyScalar<PlanarF16>`
+    fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+        bevy::render::settings::WgpuFeatures::empty()
+    }
+
+    // all bindings unpaged in `group`, at their declared binding indices
+    fn wgsl_source(group: u32, read_only: bool) -> String {
+        let partition = partition::PlanarPartition::from_entries(
+            group,
+            &Self::bind_group_layout_entries(read_only),
+        );
+        format!(
+            "{}{}",
+            wgsl_enables(Self::wgsl_features()),
+            Self::wgsl_partitioned_source(&partition, &paging::PlanarPaging::default(), read_only),
+        )
+    }
+
+    // read-write modules append `_rw`, e.g. `bevy_interleave::my_struct::storage_rw`
+    fn wgsl_module_import_path(read_only: bool) -> String {
+        if read_only {
+            Self::wgsl_import_path()
+        } else {
+            format!("{}_rw", Self::wgsl_import_path())
+        }
+    }
+
+    fn wgsl_partitioned_module(
+        partition: &partition::PlanarPartition,
+        paging: &paging::PlanarPaging,
+        read_only: bool,
+    ) -> String {
+        format!(
+            "{}#define_import_path {}\n\n{}",
+            wgsl_enables(Self::wgsl_features()),
+            Self::wgsl_module_import_path(read_only),
+            Self::wgsl_partitioned_source(partition, paging, read_only),
+        )
+    }
+
+    fn wgsl_module(group: u32, read_only: bool) -> String {
+        let partition = partition::PlanarPartition::from_entries(
+            group,
+            &Self::bind_group_layout_entries(read_only),
+        );
+        Self::wgsl_partitioned_module(&partition, &paging::PlanarPaging::default(), read_only)
+    }
 }
 
 pub trait GpuPlanarTexture
@@ -70,39 +187,200 @@ where
     Self: GpuPlanar,
     Self: bevy::render::render_asset::RenderAsset<SourceAsset = Self::PlanarType>,
 {
+    fn prepare_textures(
+        source: &Self::PlanarType,
+        render_device: &bevy::render::renderer::RenderDevice,
+        render_queue: &bevy::render::renderer::RenderQueue,
+    ) -> Self;
+
+    // note: rows beyond `texture::planar_texture_capacity` are not uploaded
+    fn check_limits(
+        limits: &bevy::render::settings::WgpuLimits,
+    ) -> Result<(), partition::PlanarLimitError>;
+
+    // one view per `bind_group_layout_entries` entry
+    fn texture_views(&self) -> Vec<bevy::render::render_resource::TextureView>;
+
     fn bind_group(
         &self,
         render_device: &bevy::render::renderer::RenderDevice,
-        gpu_images: &bevy::render::render_asset::RenderAssets<bevy::render::texture::GpuImage>,
         layout: &bevy::render::render_resource::BindGroupLayout,
     ) -> bevy::render::render_resource::BindGroup;
 
+    // note: entries follow `ReflectInterleaved::ordered_field_names`, skipping `#[planar(cpu_only)]` fields
+    fn bind_group_layout_entries() -> Vec<bevy::render::render_resource::BindGroupLayoutEntry>;
+
     fn bind_group_layout(
         render_device: &bevy::render::renderer::RenderDevice,
     ) -> bevy::render::render_resource::BindGroupLayout;
+}
 
-    fn get_asset_handles(&self) -> Vec<bevy::asset::Handle<bevy::image::Image>>;
+pub trait ReflectInterleaved {
+    type PackedType;
+
+    // per field, fields of a `#[stream(n)]` report the row size of their stream
+    fn min_binding_sizes() -> &'static [usize];
+    // columns of `#[planar(flatten)]` fields follow as dotted paths, e.g. `material.color`
+    fn ordered_field_names() -> &'static [&'static str];
+    fn wgsl_struct() -> &'static str;
 }
 
-// TODO: find a better name, PlanarTexture is implemented on the packed type
-pub trait PlanarTexture
+// note: implemented by the `PlanarEnum` derive for fieldless `#[repr(u32)]` enums, `#[planar(enum)]` fields are uploaded as `u32`
+pub trait PlanarEnum
 where
-    Self: PlanarSync,
+    Self: Copy,
+    Self: 'static,
 {
-    // note: planar texture's gpu type utilizes bevy's image render asset
-    fn prepare(
-        images: &mut bevy::asset::Assets<bevy::image::Image>,
-        planar: &Self::PlanarType,
-    ) -> Self::GpuPlanarType;
+    fn discriminant(self) -> u32;
 
-    fn get_asset_handles(&self) -> Vec<bevy::asset::Handle<bevy::image::Image>>;
+    // e.g. `const PARTICLE_KIND_SPARK: u32 = 1u;` per variant
+    fn wgsl_consts() -> String;
 }
 
-pub trait ReflectInterleaved {
-    type PackedType;
+// note: the wgsl type of a generic parameter of a planar struct, the storage derives replace e.g. `T` by `f32`
+// the wgsl array stride must equal `size_of::<Self>()`, e.g. `Vec3` is not supported
+pub trait PlanarWgslType {
+    // std430 alignment, e.g. 16 for `vec4<f32>`
+    const WGSL_ALIGN: usize;
 
-    fn min_binding_sizes() -> &'static [usize];
-    fn ordered_field_names() -> &'static [&'static str];
+    fn wgsl_type() -> &'static str;
+
+    // device features the wgsl type needs, e.g. `Features::SHADER_F16` for `f16`
+    fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+        bevy::render::settings::WgpuFeatures::empty()
+    }
+
+    // e.g. `vec2_f32`, generic planar structs get one import path per instantiation
+    fn wgsl_path_segment() -> String {
+        Self::wgsl_type()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+// `enable` directives of `features`, generated modules declare them ahead of `#define_import_path`
+pub fn wgsl_enables(features: bevy::render::settings::WgpuFeatures) -> String {
+    if features.contains(bevy::render::settings::WgpuFeatures::SHADER_F16) {
+        "enable f16;\n\n".to_string()
+    } else {
+        String::new()
+    }
+}
+
+// replaces whole identifiers of `source`, e.g. the type parameter `T` of a generated shader by `f32`
+pub fn substitute_wgsl_types(source: &str, types: &[(&str, &str)]) -> String {
+    if types.is_empty() {
+        return source.to_string();
+    }
+
+    let mut substituted = String::with_capacity(source.len());
+    let mut ident = String::new();
+
+    let flush = |ident: &mut String, substituted: &mut String| {
+        match types.iter().find(|(name, _)| *name == ident.as_str()) {
+            Some((_, wgsl_type)) => substituted.push_str(wgsl_type),
+            None => substituted.push_str(ident),
+        }
+        ident.clear();
+    };
+
+    for c in source.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else {
+            flush(&mut ident, &mut substituted);
+            substituted.push(c);
+        }
+    }
+    flush(&mut ident, &mut substituted);
+
+    substituted
+}
+
+macro_rules! impl_planar_wgsl_type {
+    ($($ty:ty => $wgsl:literal, $align:literal),* $(,)?) => {
+        $(
+            impl PlanarWgslType for $ty {
+                const WGSL_ALIGN: usize = $align;
+
+                fn wgsl_type() -> &'static str {
+                    $wgsl
+                }
+            }
+        )*
+    };
+}
+
+impl_planar_wgsl_type! {
+    f32 => "f32", 4,
+    i32 => "i32", 4,
+    u32 => "u32", 4,
+    bevy::math::Vec2 => "vec2<f32>", 8,
+    bevy::math::Vec4 => "vec4<f32>", 16,
+    bevy::math::IVec2 => "vec2<i32>", 8,
+    bevy::math::IVec4 => "vec4<i32>", 16,
+    bevy::math::UVec2 => "vec2<u32>", 8,
+    bevy::math::UVec4 => "vec4<u32>", 16,
+    bevy::math::Quat => "vec4<f32>", 16,
+    bevy::math::Mat4 => "mat4x4<f32>", 16,
+}
+
+impl PlanarWgslType for half::f16 {
+    const WGSL_ALIGN: usize = 2;
+
+    fn wgsl_type() -> &'static str {
+        "f16"
+    }
+
+    fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+        bevy::render::settings::WgpuFeatures::SHADER_F16
+    }
+}
+
+// note: `half::f16` is not reflectable, planar structs are instantiated with this wrapper instead
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    PartialOrd,
+    bevy::reflect::Reflect,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[reflect(opaque, Clone, Debug, PartialEq)]
+#[repr(transparent)]
+pub struct PlanarF16(pub half::f16);
+
+// safety: `repr(transparent)` over a `Pod` type
+unsafe impl bytemuck::Zeroable for PlanarF16 {}
+unsafe impl bytemuck::Pod for PlanarF16 {}
+
+impl From<f32> for PlanarF16 {
+    fn from(value: f32) -> Self {
+        Self(half::f16::from_f32(value))
+    }
+}
+
+impl From<PlanarF16> for f32 {
+    fn from(value: PlanarF16) -> Self {
+        value.0.to_f32()
+    }
+}
+
+impl PlanarWgslType for PlanarF16 {
+    const WGSL_ALIGN: usize = <half::f16 as PlanarWgslType>::WGSL_ALIGN;
+
+    fn wgsl_type() -> &'static str {
+        <half::f16 as PlanarWgslType>::wgsl_type()
+    }
+
+    fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+        <half::f16 as PlanarWgslType>::wgsl_features()
+    }
 }
 
 pub trait Planar
@@ -126,4 +404,20 @@ where
         Self: Sized;
 
     fn subset(&self, indices: &[usize]) -> Self;
+
+    fn dirty_ranges(&self) -> &dirty::PlanarDirtyRanges;
+    fn dirty_ranges_mut(&mut self) -> &mut dirty::PlanarDirtyRanges;
+
+    // note: writes through the `pub` columns bypass `set`, call this (or use the `<column>_mut` accessors) afterwards
+    fn mark_all_dirty(&mut self);
+
+    // note: also clears the dirty ranges of `#[planar(flatten)]` fields
+    fn clear_dirty_ranges(&mut self) {
+        self.dirty_ranges_mut().clear();
+    }
+
+    // note: `column` follows `ReflectInterleaved::ordered_field_names`
+    fn mark_dirty(&mut self, column: usize, rows: std::ops::Range<usize>) {
+        self.dirty_ranges_mut().mark(column, rows);
+    }
 }
@@ -0,0 +1,54 @@
+use std::ops::Range;
+
+// note: `Vec<T>` planar fields are stored CSR-style, a flat values column and a `u32` end offset per row,
+// row `i` spans `ends[i - 1]..ends[i]` of the values, the first row starts at 0
+
+// end offsets of consecutive lists of `lens` values
+pub fn list_ends(lens: impl IntoIterator<Item = usize>) -> Vec<u32> {
+    lens.into_iter()
+        .scan(0u32, |end, len| {
+            *end += len as u32;
+            Some(*end)
+        })
+        .collect()
+}
+
+// values of `row`
+pub fn list_range(ends: &[u32], row: usize) -> Range<usize> {
+    let start = match row {
+        0 => 0,
+        row => ends[row - 1] as usize,
+    };
+
+    start..ends[row] as usize
+}
+
+// values of the lists of `rows`, e.g. dirty rows of the values column
+pub fn list_value_rows(ends: &[u32], rows: &[Range<usize>]) -> Vec<Range<usize>> {
+    rows.iter()
+        .filter_map(|range| {
+            let end = range.end.min(ends.len());
+            (range.start < end)
+                .then(|| list_range(ends, range.start).start..list_range(ends, end - 1).end)
+        })
+        .filter(|range| !range.is_empty())
+        .collect()
+}
+
+// replaces the list of `row`, shifting the values of later rows
+// returns whether the number of values changed, moving every later row
+pub fn set_list<T: Clone>(ends: &mut [u32], values: &mut Vec<T>, row: usize, list: &[T]) -> bool {
+    let range = list_range(ends, row);
+    let resized = range.len() != list.len();
+
+    values.splice(range.clone(), list.iter().cloned());
+
+    if resized {
+        let delta = list.len() as i64 - range.len() as i64;
+        for end in &mut ends[row..] {
+            *end = (*end as i64 + delta) as u32;
+        }
+    }
+
+    resized
+}
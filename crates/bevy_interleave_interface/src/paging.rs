@@ -0,0 +1,559 @@
+use std::ops::Range;
+
+use bevy::render::{
+    render_resource::{
+        BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingResource,
+        BindingType, Buffer, BufferBinding, BufferBindingType, BufferInitDescriptor, BufferSize,
+        BufferUsages, ShaderStages,
+    },
+    renderer::{RenderDevice, RenderQueue},
+    settings::WgpuLimits,
+};
+
+use crate::{
+    dirty::{PlanarDirtyWrite, dirty_writes},
+    partition::{PlanarBindingLocation, PlanarPartition},
+    validity::PlanarValidity,
+};
+
+// splits every column into `pages` storage bindings of at most `page_len` rows
+// the number of pages is part of the bind group layout, `page_len` keeps each page within
+// `max_storage_buffer_binding_size`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlanarPaging {
+    pub pages: u32,
+    pub page_len: usize,
+}
+
+impl Default for PlanarPaging {
+    fn default() -> Self {
+        Self {
+            pages: 1,
+            page_len: u32::MAX as usize,
+        }
+    }
+}
+
+impl PlanarPaging {
+    // `strides` are the column element sizes, the widest column bounds the page length
+    pub fn new(pages: u32, max_binding_size: u64, strides: &[usize]) -> Self {
+        let page_len = strides
+            .iter()
+            .map(|stride| max_binding_size / (*stride).max(1) as u64)
+            .min()
+            .unwrap_or(u64::MAX)
+            .clamp(1, u32::MAX as u64) as usize;
+
+        Self {
+            pages: pages.max(1),
+            page_len,
+        }
+    }
+
+    pub fn from_limits(pages: u32, strides: &[usize], limits: &WgpuLimits) -> Self {
+        Self::new(pages, limits.max_storage_buffer_binding_size, strides)
+    }
+
+    // strides are taken from the `min_binding_size` of each column entry
+    pub fn from_layout_entries(
+        pages: u32,
+        column_entries: &[BindGroupLayoutEntry],
+        limits: &WgpuLimits,
+    ) -> Self {
+        let strides = column_entries.iter().map(entry_stride).collect::<Vec<_>>();
+
+        Self::from_limits(pages, &strides, limits)
+    }
+
+    // rows that fit in the bound pages, later rows are not uploaded
+    pub fn capacity(&self) -> usize {
+        self.page_len.saturating_mul(self.pages as usize)
+    }
+
+    // at least enough pages for `rows`
+    pub fn with_capacity(self, rows: usize) -> Self {
+        let pages = rows.div_ceil(self.page_len).min(u32::MAX as usize) as u32;
+
+        Self {
+            pages: self.pages.max(pages),
+            ..self
+        }
+    }
+
+    // rows of `len` beyond `capacity`
+    pub fn truncated(&self, len: usize) -> usize {
+        len.saturating_sub(self.capacity())
+    }
+
+    // called by the generated `prepare_asset`, the rows beyond `capacity` are dropped
+    pub fn warn_truncated(&self, name: &str, len: usize) {
+        let truncated = self.truncated(len);
+        if truncated > 0 {
+            bevy::log::warn!(
+                "{truncated} of {len} rows of `{name}` exceed the {} storage pages and are not uploaded, \
+                 see `PlanarStoragePlugin::with_max_rows`",
+                self.pages,
+            );
+        }
+    }
+
+    // pages holding at least one of `len` rows
+    pub fn page_count(&self, len: usize) -> u32 {
+        len.min(self.capacity()).div_ceil(self.page_len) as u32
+    }
+
+    pub fn page_rows(&self, len: usize, page: u32) -> Range<usize> {
+        let start = (page as usize).saturating_mul(self.page_len).min(len);
+        let end = start.saturating_add(self.page_len).min(len);
+        start..end
+    }
+
+    // bytes of `page` within a tightly packed column
+    pub fn page_bytes<'a>(&self, bytes: &'a [u8], stride: usize, page: u32) -> &'a [u8] {
+        let len = bytes.len().checked_div(stride).unwrap_or(0);
+        let rows = self.page_rows(len, page);
+
+        &bytes[rows.start * stride..rows.end * stride]
+    }
+
+    // the parts of `rows` within `page`, relative to the first row of the page
+    pub fn page_ranges(&self, page: u32, rows: &[Range<usize>]) -> Vec<Range<usize>> {
+        let page_start = (page as usize).saturating_mul(self.page_len);
+        let page_end = page_start.saturating_add(self.page_len);
+
+        rows.iter()
+            .map(|range| range.start.max(page_start)..range.end.min(page_end))
+            .filter(|range| !range.is_empty())
+            .map(|range| range.start - page_start..range.end - page_start)
+            .collect()
+    }
+
+    // writes of the dirty `rows` of a tightly packed column within `page`, see `dirty_writes`
+    pub fn dirty_writes<'a>(
+        &self,
+        bytes: &'a [u8],
+        stride: usize,
+        page: u32,
+        page_size: usize,
+        rows: &[Range<usize>],
+    ) -> Vec<PlanarDirtyWrite<'a>> {
+        dirty_writes(
+            self.page_bytes(bytes, stride, page),
+            stride,
+            page_size,
+            &self.page_ranges(page, rows),
+        )
+    }
+
+    // paging of a bit-packed column, each page packs its rows into its own `u32` words
+    pub fn bit_packed(&self) -> Self {
+        Self {
+            pages: self.pages,
+            page_len: self.page_len.div_ceil(32),
+        }
+    }
+
+    // packs `bits` page by page, the first row of a page is the lowest bit of its first word
+    pub fn pack_bits(&self, bits: &[bool]) -> Vec<u32> {
+        self.pack_rows(bits.len(), |row| bits[row])
+    }
+
+    // packs the validity bitmask of an `Option<T>` field like `pack_bits`
+    pub fn pack_validity(&self, validity: &PlanarValidity) -> Vec<u32> {
+        self.pack_rows(validity.len(), |row| validity.get(row))
+    }
+
+    fn pack_rows(&self, len: usize, bit: impl Fn(usize) -> bool) -> Vec<u32> {
+        let words_per_page = self.bit_packed().page_len;
+        let mut words = Vec::new();
+
+        for page in 0..self.page_count(len) {
+            let rows = self.page_rows(len, page);
+            let base = page as usize * words_per_page;
+            words.resize(base + rows.len().div_ceil(32), 0);
+
+            for (offset, row) in rows.enumerate() {
+                if bit(row) {
+                    words[base + offset / 32] |= 1 << (offset % 32);
+                }
+            }
+        }
+
+        words
+    }
+
+    // words of `pack_bits` holding `rows`
+    pub fn bit_packed_rows(&self, rows: &[Range<usize>]) -> Vec<Range<usize>> {
+        let words_per_page = self.bit_packed().page_len;
+
+        (0..self.pages)
+            .flat_map(|page| {
+                let base = page as usize * words_per_page;
+                self.page_ranges(page, rows)
+                    .into_iter()
+                    .map(move |range| base + range.start / 32..base + range.end.div_ceil(32))
+            })
+            .collect()
+    }
+
+    // repeats each column entry once per page, page-major within the column
+    pub fn expand<T: Clone>(&self, column_entries: Vec<T>) -> Vec<T> {
+        column_entries
+            .into_iter()
+            .flat_map(|entry| std::iter::repeat_n(entry, self.pages as usize))
+            .collect()
+    }
+
+    pub fn metadata(&self, len: usize) -> PlanarPageMetadata {
+        PlanarPageMetadata {
+            count: len.min(self.capacity()) as u32,
+            page_count: self.page_count(len),
+            page_len: self.page_len.min(u32::MAX as usize) as u32,
+        }
+    }
+}
+
+// element size of a column entry, i.e. its `min_binding_size`
+pub fn entry_stride(entry: &BindGroupLayoutEntry) -> usize {
+    match entry.ty {
+        BindingType::Buffer {
+            min_binding_size, ..
+        } => min_binding_size.map_or(1, |size| size.get() as usize),
+        _ => 1,
+    }
+}
+
+// storage binding of one column page, a whole buffer or a range of a single allocation
+#[derive(Clone, Debug)]
+pub struct PlanarColumnPage {
+    pub buffer: Buffer,
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl PlanarColumnPage {
+    pub fn whole(buffer: Buffer) -> Self {
+        let size = buffer.size();
+
+        Self {
+            buffer,
+            offset: 0,
+            size,
+        }
+    }
+
+    pub fn binding(&self) -> BindingResource<'_> {
+        BindingResource::Buffer(BufferBinding {
+            buffer: &self.buffer,
+            offset: self.offset,
+            size: BufferSize::new(self.size),
+        })
+    }
+}
+
+// uniform read by the generated wgsl page helper
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlanarPageMetadata {
+    pub count: u32,
+    pub page_count: u32,
+    pub page_len: u32,
+}
+
+impl PlanarPageMetadata {
+    pub const SIZE: u64 = 16;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE as usize] {
+        let mut bytes = [0; Self::SIZE as usize];
+        bytes[0..4].copy_from_slice(&self.count.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.page_count.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.page_len.to_le_bytes());
+        bytes
+    }
+}
+
+pub fn page_metadata_layout_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::VERTEX_FRAGMENT | ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: BufferSize::new(PlanarPageMetadata::SIZE),
+        },
+        count: None,
+    }
+}
+
+// uploads a tightly packed column as one buffer per page
+// `previous` pages of an asset with the same length are reused, only `rows` are written
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_column_pages(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    label: &'static str,
+    bytes: &[u8],
+    stride: usize,
+    paging: &PlanarPaging,
+    previous: Option<&[PlanarColumnPage]>,
+    rows: &[Range<usize>],
+) -> Vec<PlanarColumnPage> {
+    (0..paging.pages)
+        .map(|page| {
+            let page_bytes = paging.page_bytes(bytes, stride, page);
+
+            if let Some(previous) = previous {
+                let previous = &previous[page as usize];
+                for write in paging.dirty_writes(bytes, stride, page, previous.size as usize, rows)
+                {
+                    render_queue.write_buffer(
+                        &previous.buffer,
+                        previous.offset + write.offset as u64,
+                        &write.bytes,
+                    );
+                }
+                return previous.clone();
+            }
+
+            // note: unused pages still need a binding of at least one element
+            let contents = if page_bytes.is_empty() {
+                vec![0; stride.max(4)]
+            } else {
+                page_bytes.to_vec()
+            };
+
+            PlanarColumnPage::whole(
+                render_device.create_buffer_with_data(&BufferInitDescriptor {
+                    label: Some(label),
+                    contents: &contents,
+                    usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+                }),
+            )
+        })
+        .collect()
+}
+
+pub fn prepare_page_metadata(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    paging: &PlanarPaging,
+    len: usize,
+    previous: Option<&Buffer>,
+) -> Buffer {
+    let bytes = paging.metadata(len).to_bytes();
+
+    match previous {
+        Some(buffer) => {
+            render_queue.write_buffer(buffer, 0, &bytes);
+            buffer.clone()
+        }
+        None => render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("planar page metadata"),
+            contents: &bytes,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+        }),
+    }
+}
+
+// column entries are paged and partitioned, the page metadata uniform follows the last group's storage bindings
+pub fn paged_bind_group_layouts(
+    render_device: &RenderDevice,
+    label: &'static str,
+    column_entries: Vec<BindGroupLayoutEntry>,
+    partition: &PlanarPartition,
+    paging: &PlanarPaging,
+) -> Vec<BindGroupLayout> {
+    let column_entries = partition.sharing.entries(&column_entries);
+    let mut groups = partition.split(paging.expand(column_entries), |entry, binding| {
+        entry.binding = binding
+    });
+
+    if let Some(last) = groups.last_mut() {
+        last.push(page_metadata_layout_entry(
+            partition.trailing_location().binding,
+        ));
+    }
+
+    groups
+        .iter()
+        .map(|entries| render_device.create_bind_group_layout(Some(label), entries))
+        .collect()
+}
+
+pub fn paged_bind_groups(
+    render_device: &RenderDevice,
+    label: &'static str,
+    layouts: &[BindGroupLayout],
+    partition: &PlanarPartition,
+    columns: &[&[PlanarColumnPage]],
+    page_metadata: &Buffer,
+) -> Vec<BindGroup> {
+    // note: the columns of a shared binding hold the same pages
+    let entries = partition
+        .sharing
+        .bound(columns)
+        .into_iter()
+        .flat_map(|pages| pages.iter())
+        .map(|page| BindGroupEntry {
+            binding: 0,
+            resource: page.binding(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut groups = partition.split(entries, |entry, binding| entry.binding = binding);
+
+    if let Some(last) = groups.last_mut() {
+        last.push(BindGroupEntry {
+            binding: partition.trailing_location().binding,
+            resource: page_metadata.as_entire_binding(),
+        });
+    }
+
+    groups
+        .iter()
+        .zip(layouts)
+        .map(|(entries, layout)| render_device.create_bind_group(label, layout, entries))
+        .collect()
+}
+
+// wgsl names of one column, generated by the `StorageBindings` derive
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanarWgslColumn {
+    // first page, e.g. `my_struct_field`
+    pub binding: String,
+    // later pages append `{page}_`, e.g. `my_struct_field_page1_`
+    pub page_prefix: String,
+    // e.g. `load_my_struct_field`
+    pub loader: String,
+    pub storage: String,
+    // e.g. `#[storage(read_write)]`, follows the `read_only` argument when unset
+    pub read_only: Option<bool>,
+    // `#[storage(bit_packed)]`, `storage` is `u32` and the loader returns the row's bit
+    pub bit_packed: bool,
+    // one element per row uploaded by this struct, may be interleaved with columns of the same type, see `PlanarSharing`
+    pub shareable: bool,
+}
+
+// wgsl names of the page metadata uniform and the `(page, offset)` helper
+pub struct PlanarWgslPages {
+    pub metadata_struct: &'static str,
+    pub metadata_binding: &'static str,
+    pub page_fn: &'static str,
+}
+
+impl PlanarWgslColumn {
+    pub fn page_binding(&self, page: u32) -> String {
+        if page == 0 {
+            self.binding.clone()
+        } else {
+            format!("{}{page}_", self.page_prefix)
+        }
+    }
+
+    // e.g. `my_struct_material_value` for the `my_material_value` column of a `#[planar(flatten)]` field `material`
+    // note: nested columns are uploaded by their own struct and never shared
+    pub fn renamed(self, from: &str, to: &str) -> Self {
+        let rename = |name: &str| match name.strip_prefix(from) {
+            Some(suffix) => format!("{to}{suffix}"),
+            None => name.to_string(),
+        };
+        let loader = match self.loader.strip_prefix("load_") {
+            Some(name) => format!("load_{}", rename(name)),
+            None => self.loader.clone(),
+        };
+
+        Self {
+            binding: rename(&self.binding),
+            page_prefix: rename(&self.page_prefix),
+            loader,
+            shareable: false,
+            ..self
+        }
+    }
+}
+
+// bindings, page helper and per column loaders, `accessor` reads rows through the loaders
+pub fn wgsl_paged_source(
+    packed_struct: &str,
+    columns: &[PlanarWgslColumn],
+    pages: &PlanarWgslPages,
+    accessor: &str,
+    partition: &PlanarPartition,
+    paging: &PlanarPaging,
+    read_only: bool,
+) -> String {
+    let mut source = String::from(packed_struct);
+    source.push('\n');
+
+    let slots = partition.sharing.slots(columns.len());
+    for (idx, slot) in slots.iter().enumerate() {
+        let column = &columns[slot[0]];
+        let access = if column.read_only.unwrap_or(read_only) {
+            "read"
+        } else {
+            "read_write"
+        };
+
+        for page in 0..paging.pages {
+            let location = partition.location(idx * paging.pages as usize + page as usize);
+            source.push_str(&format!(
+                "@group({}) @binding({}) var<storage, {access}> {}: array<{}>;\n",
+                location.group,
+                location.binding,
+                column.page_binding(page),
+                column.storage,
+            ));
+        }
+    }
+
+    let PlanarBindingLocation {
+        group: metadata_group,
+        binding: metadata_binding,
+    } = partition.trailing_location();
+
+    let PlanarWgslPages {
+        metadata_struct,
+        metadata_binding: metadata_name,
+        page_fn,
+    } = pages;
+
+    source.push_str(&format!(
+        "\nstruct {metadata_struct} {{\n    count: u32,\n    page_count: u32,\n    page_len: u32,\n    padding: u32,\n}}\n\n\
+         @group({metadata_group}) @binding({metadata_binding}) var<uniform> {metadata_name}: {metadata_struct};\n\n\
+         // (page, offset) of a global row index\n\
+         fn {page_fn}(index: u32) -> vec2<u32> {{\n    return vec2<u32>(index / {metadata_name}.page_len, index % {metadata_name}.page_len);\n}}\n",
+    ));
+
+    for (idx, column) in columns.iter().enumerate() {
+        let (slot, lane, lanes) = partition.sharing.lane(columns.len(), idx);
+        let bound = &columns[slots[slot][0]];
+
+        let load = |binding: &str| {
+            if column.bit_packed {
+                format!("({binding}[page.y / 32u] >> (page.y % 32u)) & 1u")
+            } else if lanes > 1 {
+                format!("{binding}[page.y * {lanes}u + {lane}u]")
+            } else {
+                format!("{binding}[page.y]")
+            }
+        };
+
+        let mut cases = String::new();
+        for page in 1..paging.pages {
+            cases.push_str(&format!(
+                "        case {page}u: {{ return {}; }}\n",
+                load(&bound.page_binding(page)),
+            ));
+        }
+
+        source.push_str(&format!(
+            "\nfn {}(index: u32) -> {} {{\n    let page = {page_fn}(index);\n    switch page.x {{\n{cases}        default: {{ return {}; }}\n    }}\n}}\n",
+            column.loader,
+            column.storage,
+            load(&bound.binding),
+        ));
+    }
+
+    source.push('\n');
+    source.push_str(accessor);
+    source
+}
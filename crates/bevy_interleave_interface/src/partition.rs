@@ -0,0 +1,348 @@
+use std::ops::Range;
+
+use bevy::render::{
+    render_resource::{
+        BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, ShaderStages,
+    },
+    settings::WgpuLimits,
+};
+
+use crate::paging::{PlanarWgslColumn, entry_stride};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlanarBindingLocation {
+    pub group: u32,
+    pub binding: u32,
+}
+
+// contiguous runs of storage bindings (pages of each `sharing` binding), one bind group each, numbered from `first_group`
+// note: a pipeline stage counts the storage buffers of every group it binds against
+// `max_storage_buffers_per_shader_stage`, splitting only helps with `max_bindings_per_bind_group`, `sharing` with the former
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanarPartition {
+    pub first_group: u32,
+    pub groups: Vec<Range<usize>>,
+    // declared binding of each field, bindings are sequential within each group when empty
+    pub bindings: Vec<u32>,
+    // columns interleaved into one binding, see `PlanarSharing::pack`
+    pub sharing: PlanarSharing,
+}
+
+impl PlanarPartition {
+    pub fn single(group: u32, field_count: usize) -> Self {
+        Self {
+            first_group: group,
+            groups: std::iter::once(0..field_count).collect(),
+            bindings: Vec::new(),
+            sharing: PlanarSharing::default(),
+        }
+    }
+
+    // one group keeping the bindings declared by e.g. `#[storage(binding = 5)]`
+    pub fn from_entries(group: u32, entries: &[BindGroupLayoutEntry]) -> Self {
+        Self {
+            bindings: entries.iter().map(|entry| entry.binding).collect(),
+            ..Self::single(group, entries.len())
+        }
+    }
+
+    pub fn new(first_group: u32, field_count: usize, max_bindings_per_group: u32) -> Self {
+        let max_bindings_per_group = (max_bindings_per_group as usize).max(1);
+
+        let groups = (0..field_count)
+            .step_by(max_bindings_per_group)
+            .map(|start| start..(start + max_bindings_per_group).min(field_count))
+            .collect::<Vec<_>>();
+
+        if groups.is_empty() {
+            return Self::single(first_group, 0);
+        }
+
+        Self {
+            first_group,
+            groups,
+            bindings: Vec::new(),
+            sharing: PlanarSharing::default(),
+        }
+    }
+
+    // the page metadata uniform takes one binding of the last group
+    // note: groups only split at `max_bindings_per_bind_group`, the storage buffers of every group count per stage
+    pub fn from_limits(first_group: u32, field_count: usize, limits: &WgpuLimits) -> Self {
+        Self::new(
+            first_group,
+            field_count,
+            limits.max_bindings_per_bind_group.saturating_sub(1),
+        )
+    }
+
+    pub fn with_sharing(self, sharing: PlanarSharing) -> Self {
+        Self { sharing, ..self }
+    }
+
+    // `entries` span every binding page, e.g. `PlanarPaging::expand` of the shared read-only column entries
+    pub fn check_limits(
+        &self,
+        entries: &[BindGroupLayoutEntry],
+        limits: &WgpuLimits,
+    ) -> Result<(), PlanarLimitError> {
+        for stage in [
+            ShaderStages::VERTEX,
+            ShaderStages::FRAGMENT,
+            ShaderStages::COMPUTE,
+        ] {
+            let required = entries
+                .iter()
+                .filter(|entry| entry.visibility.contains(stage))
+                .filter(|entry| {
+                    matches!(
+                        entry.ty,
+                        BindingType::Buffer {
+                            ty: BufferBindingType::Storage { .. },
+                            ..
+                        }
+                    )
+                })
+                .count() as u32;
+
+            if required > limits.max_storage_buffers_per_shader_stage {
+                return Err(PlanarLimitError::StorageBuffersPerStage {
+                    stage,
+                    required,
+                    max: limits.max_storage_buffers_per_shader_stage,
+                });
+            }
+        }
+
+        let required = self.first_group + self.group_count() as u32;
+        if required > limits.max_bind_groups {
+            return Err(PlanarLimitError::BindGroups {
+                required,
+                max: limits.max_bind_groups,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn field_count(&self) -> usize {
+        self.groups.last().map_or(0, |range| range.end)
+    }
+
+    pub fn group(&self, idx: usize) -> u32 {
+        self.first_group + idx as u32
+    }
+
+    pub fn location(&self, field: usize) -> PlanarBindingLocation {
+        let idx = self
+            .groups
+            .iter()
+            .position(|range| range.contains(&field))
+            .unwrap_or_else(|| panic!("field {field} is not part of the partition"));
+
+        let binding = match self.bindings.get(field) {
+            Some(binding) => *binding,
+            None => (field - self.groups[idx].start) as u32,
+        };
+
+        PlanarBindingLocation {
+            group: self.group(idx),
+            binding,
+        }
+    }
+
+    // first free binding after the last group, e.g. for the page metadata uniform
+    pub fn trailing_location(&self) -> PlanarBindingLocation {
+        let idx = self.group_count() - 1;
+
+        let binding = self.groups[idx]
+            .clone()
+            .map(|field| self.location(field).binding + 1)
+            .max()
+            .unwrap_or(0);
+
+        PlanarBindingLocation {
+            group: self.group(idx),
+            binding,
+        }
+    }
+
+    // splits per-field entries into per-group entries, `rebind` assigns the binding within the group
+    pub fn split<T>(&self, entries: Vec<T>, rebind: impl Fn(&mut T, u32)) -> Vec<Vec<T>> {
+        let mut entries = entries.into_iter();
+
+        self.groups
+            .iter()
+            .map(|range| {
+                range
+                    .clone()
+                    .zip(entries.by_ref())
+                    .map(|(field, mut entry)| {
+                        rebind(&mut entry, self.location(field).binding);
+                        entry
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// columns interleaved row by row into one storage binding, a shared row holds one element of each column
+// e.g. `binding[row * 2u + 1u]` reads the second of two columns, like an implicit `#[stream(n)]`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlanarSharing {
+    // columns of each shared binding in lane order, the binding takes the place of the first column
+    pub bindings: Vec<Vec<usize>>,
+}
+
+impl PlanarSharing {
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    // shares the last columns of the same wgsl type, access and stride until `max_bindings` bindings remain
+    // note: bit-packed and nested columns are never shared, see `PlanarWgslColumn::shareable`
+    pub fn pack(
+        columns: &[PlanarWgslColumn],
+        entries: &[BindGroupLayoutEntry],
+        max_bindings: usize,
+    ) -> Self {
+        let key = |column: usize| {
+            let entry = &entries[column];
+            (
+                &columns[column].storage,
+                columns[column].read_only,
+                entry.visibility,
+                entry_stride(entry),
+            )
+        };
+
+        let mut kinds: Vec<Vec<usize>> = Vec::new();
+        for column in (0..columns.len()).filter(|column| columns[*column].shareable) {
+            match kinds.iter_mut().find(|kind| key(kind[0]) == key(column)) {
+                Some(kind) => kind.push(column),
+                None => kinds.push(vec![column]),
+            }
+        }
+
+        let mut bindings = Vec::new();
+        let mut count = columns.len();
+        for kind in kinds {
+            if count <= max_bindings {
+                break;
+            }
+
+            let lanes = kind.len().min(count - max_bindings + 1);
+            if lanes > 1 {
+                bindings.push(kind[kind.len() - lanes..].to_vec());
+                count -= lanes - 1;
+            }
+        }
+
+        Self { bindings }
+    }
+
+    // columns of each binding, ordered by their first column
+    pub fn slots(&self, column_count: usize) -> Vec<Vec<usize>> {
+        (0..column_count)
+            .filter_map(|column| {
+                match self.bindings.iter().find(|shared| shared.contains(&column)) {
+                    Some(shared) if shared[0] == column => Some(shared.clone()),
+                    Some(_) => None,
+                    None => Some(vec![column]),
+                }
+            })
+            .collect()
+    }
+
+    // `(binding, lane, lanes)` of `column`
+    pub fn lane(&self, column_count: usize, column: usize) -> (usize, usize, usize) {
+        self.slots(column_count)
+            .iter()
+            .enumerate()
+            .find_map(|(binding, slot)| {
+                let lane = slot.iter().position(|other| *other == column)?;
+                Some((binding, lane, slot.len()))
+            })
+            .unwrap_or_else(|| panic!("column {column} is not part of the sharing"))
+    }
+
+    // the first column's item of each binding, e.g. its pages or binding name
+    pub fn bound<T: Clone>(&self, columns: &[T]) -> Vec<T> {
+        self.slots(columns.len())
+            .iter()
+            .map(|slot| columns[slot[0]].clone())
+            .collect()
+    }
+
+    // one entry per binding, a shared row spans the element of every lane
+    pub fn entries(&self, entries: &[BindGroupLayoutEntry]) -> Vec<BindGroupLayoutEntry> {
+        self.slots(entries.len())
+            .iter()
+            .map(|slot| {
+                let mut entry = entries[slot[0]];
+                if let BindingType::Buffer {
+                    min_binding_size, ..
+                } = &mut entry.ty
+                {
+                    *min_binding_size =
+                        BufferSize::new((entry_stride(&entries[slot[0]]) * slot.len()) as u64);
+                }
+                entry
+            })
+            .collect()
+    }
+}
+
+// a device limit exceeded by the storage or texture bindings of a planar type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanarLimitError {
+    // counted across every bind group of the pipeline
+    StorageBuffersPerStage {
+        stage: ShaderStages,
+        required: u32,
+        max: u32,
+    },
+    // `first_group` plus the number of groups
+    BindGroups {
+        required: u32,
+        max: u32,
+    },
+    // layers of the widest element of a texture column
+    TextureArrayLayers {
+        required: u32,
+        max: u32,
+    },
+}
+
+impl std::fmt::Display for PlanarLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanarLimitError::StorageBuffersPerStage {
+                stage,
+                required,
+                max,
+            } => write!(
+                f,
+                "{required} storage buffers are visible to the {stage:?} stage but `max_storage_buffers_per_shader_stage` is {max}, \
+                 columns of different types can not share a binding, interleave fields with `#[stream(n)]`, \
+                 use `PackedStorageBindings` or fewer pages",
+            ),
+            PlanarLimitError::BindGroups { required, max } => write!(
+                f,
+                "{required} bind groups are required but `max_bind_groups` is {max}, use a lower first bind group",
+            ),
+            PlanarLimitError::TextureArrayLayers { required, max } => write!(
+                f,
+                "{required} texture layers are required per element but `max_texture_array_layers` is {max}, \
+                 use a wider texture format or `StorageBindings`",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlanarLimitError {}
@@ -0,0 +1,66 @@
+// bytes of one field copied from its rust representation into a std430 struct member
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Std430Segment {
+    // offset within the rust value
+    pub host: usize,
+    // offset within the std430 member
+    pub offset: usize,
+    pub len: usize,
+    // `bool` bytes are widened to `u32`
+    pub widen_bool: bool,
+}
+
+// a planar column interleaved into std430 rows by `interleave_std430`
+pub struct Std430Column<'a> {
+    pub bytes: &'a [u8],
+    // `size_of` the rust field
+    pub host_size: usize,
+    // member offset within the std430 struct
+    pub offset: usize,
+    pub segments: &'a [Std430Segment],
+}
+
+// member offsets and array stride of a std430 struct of `(align, size)` members
+pub const fn std430_layout<const N: usize>(members: [(usize, usize); N]) -> ([usize; N], usize) {
+    let mut offsets = [0; N];
+    let mut cursor = 0usize;
+    let mut align = 4;
+
+    let mut idx = 0;
+    while idx < N {
+        let (member_align, size) = members[idx];
+        offsets[idx] = cursor.next_multiple_of(member_align);
+        cursor = offsets[idx] + size;
+        if member_align > align {
+            align = member_align;
+        }
+        idx += 1;
+    }
+
+    (offsets, cursor.next_multiple_of(align))
+}
+
+// packs planar columns into `count` std430 rows of `stride` bytes, padding is zeroed
+pub fn interleave_std430(count: usize, stride: usize, columns: &[Std430Column]) -> Vec<u8> {
+    let mut bytes = vec![0; count * stride];
+
+    for (row, packed) in bytes.chunks_exact_mut(stride).enumerate() {
+        for column in columns {
+            let host = &column.bytes[row * column.host_size..(row + 1) * column.host_size];
+
+            for segment in column.segments {
+                let offset = column.offset + segment.offset;
+
+                if segment.widen_bool {
+                    packed[offset..offset + 4]
+                        .copy_from_slice(&(host[segment.host] as u32).to_le_bytes());
+                } else {
+                    packed[offset..offset + segment.len]
+                        .copy_from_slice(&host[segment.host..segment.host + segment.len]);
+                }
+            }
+        }
+    }
+
+    bytes
+}
@@ -1,141 +1,462 @@
 use std::marker::PhantomData;
 
-use bevy::{prelude::*, reflect::GetTypeRegistration};
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    render::{
+        render_resource::{
+            BindGroupLayoutEntry, BindingType, BufferBindingType, DownlevelFlags, ShaderStages,
+        },
+        settings::WgpuLimits,
+    },
+};
 
-use crate::{GpuPlanarStorage, PlanarHandle, PlanarSync};
+use crate::{
+    GpuPlanarStorage, PlanarHandle, PlanarStorage, PlanarSync,
+    allocation::PlanarAllocation,
+    paging::{PlanarPaging, entry_stride, page_metadata_layout_entry},
+    partition::{PlanarLimitError, PlanarPartition, PlanarSharing},
+    sync::{PlanarSyncPlugin, clear_extracted_dirty_ranges, remove_orphaned_bind_groups},
+    validation::{PlanarBindingMismatch, validate_storage_bindings},
+};
 
 pub struct PlanarStoragePlugin<R> {
+    pub bind_group: u32,
+    // storage bindings per column, rows beyond `pages * max_storage_buffer_binding_size / stride` are not uploaded
+    pub pages: u32,
+    // expected row count of the largest asset, adds pages once the device limits are known
+    pub max_rows: Option<usize>,
+    // e.g. `PlanarAllocation::Single` for many small assets
+    pub allocation: PlanarAllocation,
     phantom: PhantomData<fn() -> R>,
 }
+// note: a derived `Clone` would require `R: Clone`
+impl<R> Clone for PlanarStoragePlugin<R> {
+    fn clone(&self) -> Self {
+        Self {
+            bind_group: self.bind_group,
+            pages: self.pages,
+            max_rows: self.max_rows,
+            allocation: self.allocation,
+            phantom: PhantomData,
+        }
+    }
+}
 impl<R> Default for PlanarStoragePlugin<R> {
     fn default() -> Self {
         Self {
+            bind_group: 0,
+            pages: 1,
+            max_rows: None,
+            allocation: PlanarAllocation::default(),
             phantom: PhantomData,
         }
     }
 }
 
+impl<R> PlanarStoragePlugin<R> {
+    // first bind group index of the generated `#import`able shader module, bindings beyond the device limits continue in the following groups
+    pub fn with_bind_group(bind_group: u32) -> Self {
+        Self {
+            bind_group,
+            ..Default::default()
+        }
+    }
+
+    // note: rows beyond the capacity of the pages are dropped with a warning, prefer `with_max_rows`
+    pub fn with_pages(self, pages: u32) -> Self {
+        Self {
+            pages: pages.max(1),
+            ..self
+        }
+    }
+
+    // pages are derived from `max_storage_buffer_binding_size`, at least `pages`
+    pub fn with_max_rows(self, max_rows: usize) -> Self {
+        Self {
+            max_rows: Some(max_rows),
+            ..self
+        }
+    }
+
+    pub fn with_allocation(self, allocation: PlanarAllocation) -> Self {
+        Self { allocation, ..self }
+    }
+}
+
+impl<R: PlanarStorage> PlanarStoragePlugin<R> {
+    // partition and paging of the storage bindings of `R` on a device with `limits`, checked by `PlanarAutoPlugin` before selecting storage
+    // note: the last columns of the same type share a binding while they exceed `max_storage_buffers_per_shader_stage`
+    pub fn layout(
+        &self,
+        limits: &WgpuLimits,
+    ) -> Result<(PlanarPartition, PlanarPaging), PlanarLimitError> {
+        let entries = R::GpuStorageType::bind_group_layout_entries(true);
+        let columns = R::GpuStorageType::wgsl_columns();
+        let paging = |entries: &[BindGroupLayoutEntry]| {
+            let paging = PlanarPaging::from_layout_entries(self.pages, entries, limits);
+            match self.max_rows {
+                Some(max_rows) => paging.with_capacity(max_rows),
+                None => paging,
+            }
+        };
+
+        // note: shared rows are wider, pages are recounted until sharing needs no more of them
+        let mut shared_paging = paging(&entries);
+        let sharing = loop {
+            let max_bindings =
+                limits.max_storage_buffers_per_shader_stage as usize / shared_paging.pages as usize;
+            let sharing = PlanarSharing::pack(&columns, &entries, max_bindings);
+
+            let pages = shared_paging.pages;
+            shared_paging = paging(&sharing.entries(&entries));
+            if shared_paging.pages <= pages {
+                break sharing;
+            }
+        };
+        let paging = shared_paging;
+
+        let bindings = sharing.slots(entries.len()).len();
+        let partition =
+            PlanarPartition::from_limits(self.bind_group, bindings * paging.pages as usize, limits);
+
+        // note: declared bindings are kept unless columns are paged, shared or partitioned, bindings are then sequential
+        let partition = if partition.group_count() == 1 && paging.pages == 1 && sharing.is_empty() {
+            PlanarPartition::from_entries(self.bind_group, &entries)
+        } else {
+            partition.with_sharing(sharing)
+        };
+
+        partition.check_limits(&paging.expand(partition.sharing.entries(&entries)), limits)?;
+
+        Ok((partition, paging))
+    }
+}
+
 impl<R: 'static> Plugin for PlanarStoragePlugin<R>
 where
-    R: PlanarSync + Default + GetTypeRegistration + Clone + Reflect,
-    R::GpuPlanarType: GpuPlanarStorage,
+    R: PlanarStorage + Default + GetTypeRegistration + Clone + Reflect,
 {
     fn build(&self, app: &mut App) {
-        app.register_type::<R>();
-
-        app.register_type::<R::PlanarType>();
-        app.register_type::<R::PlanarTypeHandle>();
-        app.init_asset::<R::PlanarType>();
-        app.register_asset_reflect::<R::PlanarType>();
+        if !app.is_plugin_added::<PlanarSyncPlugin<R>>() {
+            app.add_plugins(PlanarSyncPlugin::<R>::default());
+        }
 
         app.add_plugins(bevy::render::render_asset::RenderAssetPlugin::<
-            R::GpuPlanarType,
-        >::default());
-        app.add_plugins(bevy::render::sync_component::SyncComponentPlugin::<
-            R::PlanarTypeHandle,
+            R::GpuStorageType,
         >::default());
 
         let render_app = app.sub_app_mut(bevy::render::RenderApp);
-        render_app.add_systems(bevy::render::ExtractSchedule, extract_planar_handles::<R>);
+        render_app.add_systems(
+            bevy::render::ExtractSchedule,
+            clear_extracted_dirty_ranges::<R, R::GpuStorageType>
+                .after(bevy::render::render_asset::AssetExtractionSystems),
+        );
         render_app.add_systems(
             bevy::render::Render,
-            queue_gpu_storage_buffers::<R>.in_set(bevy::render::RenderSystems::PrepareBindGroups),
+            (
+                remove_orphaned_bind_groups::<R, PlanarStorageBindGroup<R>>,
+                remove_orphaned_bind_groups::<R, PlanarStorageBindGroup<R, PlanarReadWrite>>,
+                queue_gpu_storage_buffers::<R, PlanarReadOnly>,
+                queue_gpu_storage_buffers::<R, PlanarReadWrite>,
+            )
+                .in_set(bevy::render::RenderSystems::PrepareBindGroups),
         );
     }
 
+    // note: columns are paged, shared and partitioned across bind groups once the device limits are known, see `layout`
+    // the storage bindings of `R` are disabled with an error when they still exceed the limits or miss `wgsl_features`
+    // note: read-write layouts, bind groups and the `_rw` module need `DownlevelFlags::FRAGMENT_WRITABLE_STORAGE`
+    // once a writable column is visible to fragment shaders, e.g. `#[storage(visibility = fragment | compute)]`
     fn finish(&self, app: &mut App) {
-        if let Some(render_app) = app.get_sub_app_mut(bevy::render::RenderApp) {
-            render_app.init_resource::<PlanarStorageLayouts<R>>();
+        let Some(render_app) = app.get_sub_app_mut(bevy::render::RenderApp) else {
+            return;
+        };
+
+        let render_device = render_app
+            .world()
+            .resource::<bevy::render::renderer::RenderDevice>();
+
+        // e.g. `enable f16;` of a `PlanarF16` instantiation would fail to compose
+        let missing = R::GpuStorageType::wgsl_features() - render_device.features();
+        if !missing.is_empty() {
+            bevy::log::error!(
+                "`{}` needs the device features {missing:?}, its storage bindings are disabled",
+                std::any::type_name::<R>()
+            );
+            return;
         }
+
+        // note: wgpu would only report this once a pipeline binds the layouts
+        let (partition, paging) = match self.layout(&render_device.limits()) {
+            Ok(layout) => layout,
+            Err(error) => {
+                bevy::log::error!(
+                    "`{}` exceeds the device limits, its storage bindings are disabled: {error}",
+                    std::any::type_name::<R>()
+                );
+                return;
+            }
+        };
+
+        let read_write =
+            !writes_from_fragment(&R::GpuStorageType::bind_group_layout_entries(false))
+                || render_app
+                    .world()
+                    .resource::<bevy::render::renderer::RenderAdapter>()
+                    .get_downlevel_capabilities()
+                    .flags
+                    .contains(DownlevelFlags::FRAGMENT_WRITABLE_STORAGE);
+
+        let layouts =
+            PlanarStorageLayouts::<R>::new(render_device, partition.clone(), paging, read_write)
+                .with_allocation(self.allocation);
+        render_app.insert_resource(layouts);
+
+        let mut shaders = app
+            .world_mut()
+            .resource_mut::<Assets<bevy::shader::Shader>>();
+        let mut add_module = |read_only: bool| {
+            shaders.add(bevy::shader::Shader::from_wgsl(
+                R::GpuStorageType::wgsl_partitioned_module(&partition, &paging, read_only),
+                R::GpuStorageType::wgsl_module_import_path(read_only),
+            ))
+        };
+
+        let shader = add_module(true);
+        let read_write_shader = read_write.then(|| add_module(false));
+        app.insert_resource(PlanarStorageShader::<R> {
+            shader,
+            read_write_shader,
+            phantom: PhantomData,
+        });
     }
 }
 
-// TODO: migrate to PlanarLayouts<R: PlanarSync>
+// writable storage visible to fragment shaders, which needs `DownlevelFlags::FRAGMENT_WRITABLE_STORAGE`
+pub fn writes_from_fragment(entries: &[BindGroupLayoutEntry]) -> bool {
+    entries.iter().any(|entry| {
+        entry.visibility.contains(ShaderStages::FRAGMENT)
+            && matches!(
+                entry.ty,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    ..
+                }
+            )
+    })
+}
+
+// keeps the generated `#define_import_path` modules of `R` alive
 #[derive(bevy::prelude::Resource)]
-pub struct PlanarStorageLayouts<R: PlanarSync>
-where
-    R::GpuPlanarType: GpuPlanarStorage,
-{
-    pub bind_group_layout: bevy::render::render_resource::BindGroupLayout,
+pub struct PlanarStorageShader<R: PlanarSync> {
+    pub shader: Handle<bevy::shader::Shader>,
+    // e.g. `bevy_interleave::my_struct::storage_rw`, `None` when fragment writes lack `DownlevelFlags::FRAGMENT_WRITABLE_STORAGE`
+    pub read_write_shader: Option<Handle<bevy::shader::Shader>>,
     pub phantom: PhantomData<fn() -> R>,
 }
 
-impl<R: PlanarSync> FromWorld for PlanarStorageLayouts<R>
-where
-    R::GpuPlanarType: GpuPlanarStorage,
-{
-    fn from_world(world: &mut World) -> Self {
-        let render_device = world.resource::<bevy::render::renderer::RenderDevice>();
+// access mode of a `PlanarStorageBindGroup`, fields with a `#[storage(read_only)]` or `#[storage(read_write)]` attribute keep it in both modes
+pub trait PlanarStorageAccess: Send + Sync + 'static {
+    const READ_ONLY: bool;
+}
 
-        let read_only = true;
-        let bind_group_layout = R::GpuPlanarType::bind_group_layout(render_device, read_only);
+// e.g. render passes
+pub struct PlanarReadOnly;
+impl PlanarStorageAccess for PlanarReadOnly {
+    const READ_ONLY: bool = true;
+}
 
-        Self {
-            bind_group_layout,
-            phantom: PhantomData,
-        }
-    }
+// e.g. compute passes mutating the planar columns
+pub struct PlanarReadWrite;
+impl PlanarStorageAccess for PlanarReadWrite {
+    const READ_ONLY: bool = false;
 }
 
-#[derive(bevy::prelude::Component, Clone, Debug)]
-pub struct PlanarStorageBindGroup<R: PlanarSync> {
-    pub bind_group: bevy::render::render_resource::BindGroup,
+// TODO: migrate to PlanarLayouts<R: PlanarSync>
+#[derive(bevy::prelude::Resource)]
+pub struct PlanarStorageLayouts<R: PlanarStorage> {
+    // one read-only layout per `partition` group
+    pub bind_group_layouts: Vec<bevy::render::render_resource::BindGroupLayout>,
+    // `None` when a writable column is visible to fragment shaders and the adapter does not support it
+    pub read_write_bind_group_layouts: Option<Vec<bevy::render::render_resource::BindGroupLayout>>,
+    // spans every column page
+    pub partition: PlanarPartition,
+    pub paging: PlanarPaging,
+    pub allocation: PlanarAllocation,
     pub phantom: PhantomData<fn() -> R>,
 }
 
-fn extract_planar_handles<R>(
-    mut commands: Commands,
-    mut main_world: ResMut<bevy::render::MainWorld>,
-) where
-    R: PlanarSync + Default + Clone + Reflect,
-    R::PlanarType: Asset,
-    R::GpuPlanarType: GpuPlanarStorage,
-{
-    let mut planar_handles_query =
-        main_world.query::<(bevy::render::sync_world::RenderEntity, &R::PlanarTypeHandle)>();
+impl<R: PlanarStorage> PlanarStorageLayouts<R> {
+    pub fn new(
+        render_device: &bevy::render::renderer::RenderDevice,
+        partition: PlanarPartition,
+        paging: PlanarPaging,
+        read_write: bool,
+    ) -> Self {
+        let bind_group_layouts =
+            R::GpuStorageType::bind_group_layouts(render_device, true, &partition, &paging);
+        let read_write_bind_group_layouts = read_write.then(|| {
+            R::GpuStorageType::bind_group_layouts(render_device, false, &partition, &paging)
+        });
+
+        Self {
+            bind_group_layouts,
+            read_write_bind_group_layouts,
+            partition,
+            paging,
+            allocation: PlanarAllocation::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn with_allocation(self, allocation: PlanarAllocation) -> Self {
+        Self { allocation, ..self }
+    }
 
-    for (entity, planar_handle) in planar_handles_query.iter(&main_world) {
-        if let Ok(mut entity_commands) = commands.get_entity(entity) {
-            entity_commands.insert(planar_handle.clone());
+    pub fn layouts<A: PlanarStorageAccess>(
+        &self,
+    ) -> Option<&[bevy::render::render_resource::BindGroupLayout]> {
+        if A::READ_ONLY {
+            Some(&self.bind_group_layouts)
+        } else {
+            self.read_write_bind_group_layouts.as_deref()
         }
     }
+
+    // compares a user shader's `@group(group)` globals against the read-only layout, no GPU required
+    pub fn validate_shader(module: &naga::Module, group: u32) -> Vec<PlanarBindingMismatch> {
+        let partition = PlanarPartition::from_entries(
+            group,
+            &R::GpuStorageType::bind_group_layout_entries(true),
+        );
+        Self::validate_partitioned_shader(module, &partition, &PlanarPaging::default())
+    }
+
+    pub fn validate_partitioned_shader(
+        module: &naga::Module,
+        partition: &PlanarPartition,
+        paging: &PlanarPaging,
+    ) -> Vec<PlanarBindingMismatch> {
+        let column_entries = R::GpuStorageType::bind_group_layout_entries(true);
+        let mut field_names =
+            paging.expand(partition.sharing.bound(R::GpuStorageType::binding_names()));
+        // note: a shared binding is an `array<T>` of the element of one column
+        let mut strides = paging.expand(
+            partition
+                .sharing
+                .bound(&column_entries.iter().map(entry_stride).collect::<Vec<_>>()),
+        );
+        let column_entries = partition.sharing.entries(&column_entries);
+
+        let entries = partition.split(paging.expand(column_entries), |entry, binding| {
+            entry.binding = binding
+        });
+
+        let metadata_group = partition.group_count() - 1;
+        let metadata_binding = partition.trailing_location().binding;
+        field_names.push("page_metadata");
+        strides.push(0);
+
+        partition
+            .groups
+            .iter()
+            .zip(entries)
+            .enumerate()
+            .flat_map(|(idx, (range, mut entries))| {
+                let mut range = range.clone();
+                if idx == metadata_group {
+                    entries.push(page_metadata_layout_entry(metadata_binding));
+                    range.end += 1;
+                }
+
+                validate_storage_bindings(
+                    module,
+                    partition.group(idx),
+                    &entries,
+                    &field_names[range.clone()],
+                    &strides[range],
+                )
+            })
+            .collect()
+    }
+}
+
+// note: both access modes share the same buffers, so an entity can be written by a compute pass and read by a later render pass
+#[derive(bevy::prelude::Component, Clone, Debug)]
+pub struct PlanarStorageBindGroup<R: PlanarSync, A: PlanarStorageAccess = PlanarReadOnly> {
+    // bound at `PlanarStorageLayouts::partition` groups, in order
+    pub bind_groups: Vec<bevy::render::render_resource::BindGroup>,
+    pub asset_id: AssetId<R::PlanarType>,
+    pub phantom: PhantomData<fn() -> (R, A)>,
 }
 
-fn queue_gpu_storage_buffers<R>(
+type PlanarStorageQueryData<R, A> = (
+    Entity,
+    &'static <R as PlanarSync>::PlanarTypeHandle,
+    Option<&'static PlanarStorageBindGroup<R, A>>,
+);
+
+fn queue_gpu_storage_buffers<R, A>(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     render_device: ResMut<bevy::render::renderer::RenderDevice>,
-    gpu_planars: Res<bevy::render::render_asset::RenderAssets<R::GpuPlanarType>>,
-    bind_group_layout: Res<PlanarStorageLayouts<R>>,
-    clouds: Query<
-        (bevy::prelude::Entity, &R::PlanarTypeHandle),
-        Without<PlanarStorageBindGroup<R>>,
-    >,
+    gpu_planars: Res<bevy::render::render_asset::RenderAssets<R::GpuStorageType>>,
+    extracted_planars: Res<bevy::render::render_asset::ExtractedAssets<R::GpuStorageType>>,
+    layouts: Option<Res<PlanarStorageLayouts<R>>>,
+    clouds: Query<PlanarStorageQueryData<R, A>>,
 ) where
-    R: PlanarSync + Default + Clone + Reflect,
+    R: PlanarStorage,
+    A: PlanarStorageAccess,
     R::PlanarType: Asset,
-    R::GpuPlanarType: GpuPlanarStorage,
 {
-    let layout = &bind_group_layout.bind_group_layout;
+    // note: missing when the storage bindings exceed the device limits
+    let Some(layouts) = layouts else {
+        return;
+    };
+    let Some(access_layouts) = layouts.layouts::<A>() else {
+        return;
+    };
 
-    for (entity, planar_handle) in clouds.iter() {
-        if let Some(load_state) = asset_server.get_load_state(planar_handle.handle())
-            && load_state.is_loading()
-        {
+    for (entity, planar_handle, existing) in clouds.iter() {
+        let asset_id = planar_handle.handle().id();
+
+        let Some(gpu_planar) = gpu_planars.get(asset_id) else {
+            if existing.is_some() {
+                commands
+                    .entity(entity)
+                    .remove::<PlanarStorageBindGroup<R, A>>();
+            }
+            continue;
+        };
+
+        // the render asset is re-prepared with new buffers whenever the source asset is added or modified
+        let stale = match existing {
+            Some(bind_group) => {
+                bind_group.asset_id != asset_id || extracted_planars.added.contains(&asset_id)
+            }
+            None => true,
+        };
+
+        if !stale {
             continue;
         }
 
-        if gpu_planars.get(planar_handle.handle()).is_none() {
+        if let Some(load_state) = asset_server.get_load_state(asset_id)
+            && load_state.is_loading()
+        {
             continue;
         }
 
-        let gpu_planar: &<R as PlanarSync>::GpuPlanarType =
-            gpu_planars.get(planar_handle.handle()).unwrap();
-        let bind_group = gpu_planar.bind_group(&render_device, layout);
+        let bind_groups =
+            gpu_planar.bind_groups(&render_device, access_layouts, &layouts.partition);
 
-        commands.entity(entity).insert(PlanarStorageBindGroup::<R> {
-            bind_group,
-            phantom: PhantomData,
-        });
+        commands
+            .entity(entity)
+            .insert(PlanarStorageBindGroup::<R, A> {
+                bind_groups,
+                asset_id,
+                phantom: PhantomData,
+            });
     }
 }
@@ -0,0 +1,101 @@
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, reflect::GetTypeRegistration};
+
+use crate::{Planar, PlanarHandle, PlanarSync};
+
+// main world asset and handle sync shared by every GPU backend of `R`, added once by the backend plugins
+pub struct PlanarSyncPlugin<R> {
+    phantom: PhantomData<fn() -> R>,
+}
+impl<R> Default for PlanarSyncPlugin<R> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<R: 'static> Plugin for PlanarSyncPlugin<R>
+where
+    R: PlanarSync + Default + GetTypeRegistration + Clone + Reflect,
+{
+    fn build(&self, app: &mut App) {
+        app.register_type::<R>();
+
+        // note: `<Name>Packed` markers share the planar asset and handle of `Name`
+        if app.is_plugin_added::<
+            bevy::render::sync_component::SyncComponentPlugin<R::PlanarTypeHandle>,
+        >() {
+            return;
+        }
+
+        app.register_type::<R::PlanarType>();
+        app.register_type::<R::PlanarTypeHandle>();
+        app.init_asset::<R::PlanarType>();
+        app.register_asset_reflect::<R::PlanarType>();
+
+        app.add_plugins(bevy::render::sync_component::SyncComponentPlugin::<
+            R::PlanarTypeHandle,
+        >::default());
+
+        let render_app = app.sub_app_mut(bevy::render::RenderApp);
+        render_app.add_systems(bevy::render::ExtractSchedule, extract_planar_handles::<R>);
+    }
+}
+
+fn extract_planar_handles<R>(
+    mut commands: Commands,
+    mut main_world: ResMut<bevy::render::MainWorld>,
+) where
+    R: PlanarSync,
+{
+    let mut planar_handles_query =
+        main_world.query::<(bevy::render::sync_world::RenderEntity, &R::PlanarTypeHandle)>();
+    let planars = main_world.resource::<Assets<R::PlanarType>>();
+
+    for (entity, planar_handle) in planar_handles_query.iter(&main_world) {
+        if let Ok(mut entity_commands) = commands.get_entity(entity) {
+            // a handle to a removed (or not yet loaded) asset has nothing to bind
+            if planars.contains(planar_handle.handle()) {
+                entity_commands.insert(planar_handle.clone());
+            } else {
+                entity_commands.remove::<R::PlanarTypeHandle>();
+            }
+        }
+    }
+}
+
+// dirty ranges are consumed by `prepare_asset`, reset them on the main world copy once extracted
+pub(crate) fn clear_extracted_dirty_ranges<R, A>(
+    extracted_planars: Res<bevy::render::render_asset::ExtractedAssets<A>>,
+    mut main_world: ResMut<bevy::render::MainWorld>,
+) where
+    R: PlanarSync,
+    A: bevy::render::render_asset::RenderAsset<SourceAsset = R::PlanarType>,
+{
+    if extracted_planars.added.is_empty() {
+        return;
+    }
+
+    let mut planars = main_world.resource_mut::<Assets<R::PlanarType>>();
+    for asset_id in extracted_planars.added.iter() {
+        if let Some(planar) = planars.get_mut_untracked(*asset_id) {
+            planar.clear_dirty_ranges();
+        }
+    }
+}
+
+type OrphanedBindGroupFilter<R, C> = (With<C>, Without<<R as PlanarSync>::PlanarTypeHandle>);
+
+pub(crate) fn remove_orphaned_bind_groups<R, C>(
+    mut commands: Commands,
+    orphaned: Query<Entity, OrphanedBindGroupFilter<R, C>>,
+) where
+    R: PlanarSync,
+    C: Component,
+{
+    for entity in orphaned.iter() {
+        commands.entity(entity).remove::<C>();
+    }
+}
@@ -3,20 +3,27 @@ use std::marker::PhantomData;
 use bevy::{
     prelude::*,
     reflect::GetTypeRegistration,
-    render::extract_component::{
-        ExtractComponent,
-        ExtractComponentPlugin,
+    render::{
+        render_resource::{Extent3d, TextureFormat},
+        settings::WgpuLimits,
     },
 };
 
 use crate::{
-    GpuPlanarTexture, PlanarHandle, PlanarTexture
+    GpuPlanarTexture, PlanarHandle, PlanarSync, PlanarTexture,
+    partition::PlanarLimitError,
+    sync::{PlanarSyncPlugin, clear_extracted_dirty_ranges, remove_orphaned_bind_groups},
 };
 
-
 pub struct PlanarTexturePlugin<R> {
     phantom: PhantomData<fn() -> R>,
 }
+// note: a derived `Clone` would require `R: Clone`
+impl<R> Clone for PlanarTexturePlugin<R> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
 impl<R> Default for PlanarTexturePlugin<R> {
     fn default() -> Self {
         Self {
@@ -25,151 +32,247 @@ impl<R> Default for PlanarTexturePlugin<R> {
     }
 }
 
-impl<R> Plugin for PlanarTexturePlugin<R>
+impl<R: 'static> Plugin for PlanarTexturePlugin<R>
 where
-    R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect,
-    R::PlanarType: Asset,
-    R::GpuPlanarType: GpuPlanarTexture,
+    R: PlanarTexture + Default + GetTypeRegistration + Clone + Reflect,
 {
     fn build(&self, app: &mut App) {
-        app.register_type::<R>();
-
-        app.add_plugins(ExtractComponentPlugin::<R>::default());
+        if !app.is_plugin_added::<PlanarSyncPlugin<R>>() {
+            app.add_plugins(PlanarSyncPlugin::<R>::default());
+        }
 
-        app.add_systems(Update, prepare_textures::<R>);
+        app.add_plugins(bevy::render::render_asset::RenderAssetPlugin::<
+            R::GpuTextureType,
+        >::default());
 
         let render_app = app.sub_app_mut(bevy::render::RenderApp);
+        render_app.add_systems(
+            bevy::render::ExtractSchedule,
+            clear_extracted_dirty_ranges::<R, R::GpuTextureType>
+                .after(bevy::render::render_asset::AssetExtractionSystems),
+        );
         render_app.add_systems(
             bevy::render::Render,
-            queue_gpu_texture_buffers::<R>.in_set(bevy::render::RenderSet::PrepareAssets),
+            (
+                remove_orphaned_bind_groups::<R, PlanarTextureBindGroup<R>>,
+                queue_gpu_texture_buffers::<R>,
+            )
+                .in_set(bevy::render::RenderSystems::PrepareBindGroups),
         );
     }
 
     fn finish(&self, app: &mut App) {
-        if let Some(render_app) = app.get_sub_app_mut(bevy::render::RenderApp) {
-            render_app.init_resource::<PlanarTextureLayouts::<R>>();
+        let Some(render_app) = app.get_sub_app_mut(bevy::render::RenderApp) else {
+            return;
+        };
+
+        let limits = render_app
+            .world()
+            .resource::<bevy::render::renderer::RenderDevice>()
+            .limits();
+
+        // note: wgpu would only report this once the first texture is created
+        if let Err(error) = R::GpuTextureType::check_limits(&limits) {
+            panic!(
+                "`{}` exceeds the device limits: {error}",
+                std::any::type_name::<R>()
+            );
         }
+
+        render_app.init_resource::<PlanarTextureLayouts<R>>();
     }
 }
 
-
 #[derive(bevy::prelude::Resource)]
-pub struct PlanarTextureLayouts<R>
-where
-    R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect,
-    R::GpuPlanarType: GpuPlanarTexture,
-{
+pub struct PlanarTextureLayouts<R: PlanarTexture> {
     pub bind_group_layout: bevy::render::render_resource::BindGroupLayout,
     pub phantom: PhantomData<fn() -> R>,
 }
 
-impl<R> FromWorld for PlanarTextureLayouts<R>
-where
-    R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect,
-    R::GpuPlanarType: GpuPlanarTexture,
-{
+impl<R: PlanarTexture> FromWorld for PlanarTextureLayouts<R> {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<bevy::render::renderer::RenderDevice>();
 
-        let bind_group_layout = R::GpuPlanarType::bind_group_layout(
-            render_device,
-        );
+        let bind_group_layout = R::GpuTextureType::bind_group_layout(render_device);
 
-        PlanarTextureLayouts::<R> {
+        Self {
             bind_group_layout,
             phantom: PhantomData,
         }
     }
 }
 
+#[derive(bevy::prelude::Component, Clone, Debug)]
+pub struct PlanarTextureBindGroup<R: PlanarSync> {
+    pub bind_group: bevy::render::render_resource::BindGroup,
+    pub asset_id: AssetId<R::PlanarType>,
+    pub phantom: PhantomData<fn() -> R>,
+}
 
-// TODO: utilize asset prepare workflow
-fn prepare_textures<R>(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    cloud_res: Res<Assets<R::PlanarType>>,
-    mut images: ResMut<Assets<Image>>,
-    clouds: Query<
-        (
-            Entity,
-            &R::PlanarTypeHandle,
-        ),
-        Without<R>,
-    >,
-)
-where
-    R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect,
-    R::PlanarType: Asset,
-    R::GpuPlanarType: GpuPlanarTexture,
-{
-    for (entity, cloud_handle) in clouds.iter() {
-        if let Some(load_state) = asset_server.get_load_state(cloud_handle.handle()) {
-            if load_state.is_loading() {
-                continue;
-            }
-        }
+// one texel row-major square per layer, elements wider than a texel continue on the next layer
+pub fn planar_texture_extent(count: usize, element_size: usize, format: TextureFormat) -> Extent3d {
+    let square = (count as f32).sqrt().ceil().max(1.0) as u32;
 
-        if cloud_res.get(cloud_handle.handle()).is_none() {
-            continue;
-        }
+    Extent3d {
+        width: square,
+        height: square,
+        depth_or_array_layers: planar_texture_layers(element_size, format),
+    }
+}
 
-        let cloud = cloud_res.get(cloud_handle.handle()).unwrap();
-        let buffers = R::prepare(&mut images, cloud);
+pub fn planar_texture_layers(element_size: usize, format: TextureFormat) -> u32 {
+    let texel_size = format.block_copy_size(None).unwrap() as usize;
+    element_size.div_ceil(texel_size).max(1) as u32
+}
+
+pub fn check_planar_texture_layers(
+    element_size: usize,
+    format: TextureFormat,
+    limits: &WgpuLimits,
+) -> Result<(), PlanarLimitError> {
+    let required = planar_texture_layers(element_size, format);
 
-        // TODO: the flow is:
-        // 1. given a planar asset handle, prepare main world image buffers insert handles into main world
-        // 2. copy the main world struct directly to gpu world via extract component
-        // 3. utilize gpu_planar_type to handle both main and gpu world handles
-        commands.entity(entity).insert(buffers);
+    match required > limits.max_texture_array_layers {
+        true => Err(PlanarLimitError::TextureArrayLayers {
+            required,
+            max: limits.max_texture_array_layers,
+        }),
+        false => Ok(()),
     }
 }
 
+// rows that fit in a square of `max_texture_dimension_2d`, later rows are not uploaded
+pub fn planar_texture_capacity(limits: &WgpuLimits) -> usize {
+    let dimension = limits.max_texture_dimension_2d as usize;
+    dimension.saturating_mul(dimension)
+}
 
-#[derive(bevy::prelude::Component, Clone, Debug)]
-pub struct PlanarTextureBindGroup<R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect> {
-    pub bind_group: bevy::render::render_resource::BindGroup,
-    pub phantom: PhantomData<fn() -> R>,
+// reorders tightly packed elements into layer-major texel data, zero padded to the full extent
+pub fn planar_texture_data(
+    bytes: &[u8],
+    element_size: usize,
+    format: TextureFormat,
+) -> (Extent3d, Vec<u8>) {
+    let count = bytes.len().checked_div(element_size).unwrap_or(0);
+    let extent = planar_texture_extent(count, element_size, format);
+
+    let texel_size = format.block_copy_size(None).unwrap() as usize;
+    let layer_size = (extent.width * extent.height) as usize * texel_size;
+    let mut data = vec![0; layer_size * extent.depth_or_array_layers as usize];
+
+    for (index, element) in bytes.chunks_exact(element_size).enumerate() {
+        for (layer, texel) in element.chunks(texel_size).enumerate() {
+            let offset = layer * layer_size + index * texel_size;
+            data[offset..offset + texel.len()].copy_from_slice(texel);
+        }
+    }
+
+    (extent, data)
 }
 
+pub fn create_planar_texture(
+    render_device: &bevy::render::renderer::RenderDevice,
+    render_queue: &bevy::render::renderer::RenderQueue,
+    label: &'static str,
+    format: TextureFormat,
+    bytes: &[u8],
+    element_size: usize,
+) -> bevy::render::render_resource::Texture {
+    let len = bytes.len().checked_div(element_size).unwrap_or(0);
+    let capacity = planar_texture_capacity(&render_device.limits());
+    if len > capacity {
+        bevy::log::warn!(
+            "{} of {len} rows of `{label}` exceed `max_texture_dimension_2d` and are not uploaded",
+            len - capacity,
+        );
+    }
+
+    let bytes = &bytes[..len.min(capacity) * element_size];
+    let (size, data) = planar_texture_data(bytes, element_size, format);
+
+    render_device.create_texture_with_data(
+        render_queue,
+        &bevy::render::render_resource::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: bevy::render::render_resource::TextureDimension::D2,
+            format,
+            usage: bevy::render::render_resource::TextureUsages::TEXTURE_BINDING
+                | bevy::render::render_resource::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        bevy::render::render_resource::TextureDataOrder::LayerMajor,
+        &data,
+    )
+}
+
+pub fn planar_texture_view_dimension(
+    element_size: usize,
+    format: TextureFormat,
+) -> bevy::render::render_resource::TextureViewDimension {
+    if planar_texture_layers(element_size, format) == 1 {
+        bevy::render::render_resource::TextureViewDimension::D2
+    } else {
+        bevy::render::render_resource::TextureViewDimension::D2Array
+    }
+}
+
+type PlanarTextureQueryData<R> = (
+    Entity,
+    &'static <R as PlanarSync>::PlanarTypeHandle,
+    Option<&'static PlanarTextureBindGroup<R>>,
+);
 
 fn queue_gpu_texture_buffers<R>(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     render_device: ResMut<bevy::render::renderer::RenderDevice>,
-    gpu_images: Res<bevy::render::render_asset::RenderAssets<bevy::render::texture::GpuImage>>,
+    gpu_planars: Res<bevy::render::render_asset::RenderAssets<R::GpuTextureType>>,
+    extracted_planars: Res<bevy::render::render_asset::ExtractedAssets<R::GpuTextureType>>,
     bind_group_layout: Res<PlanarTextureLayouts<R>>,
-    clouds: Query<
-        (
-            Entity,
-            &R,
-        ),
-        Without<PlanarTextureBindGroup::<R>>,
-    >,
-)
-where
-    R: PlanarTexture + Default + Component + ExtractComponent + GetTypeRegistration + Clone + Reflect,
-    R::PlanarType: Asset,
-    R::GpuPlanarType: GpuPlanarTexture,
+    clouds: Query<PlanarTextureQueryData<R>>,
+) where
+    R: PlanarTexture,
 {
     let layout = &bind_group_layout.bind_group_layout;
 
-    for (entity, texture_buffers,) in clouds.iter() {
-        let not_ready = texture_buffers.get_asset_handles().iter()
-            .map(|handle| gpu_images.get(handle).is_none())
-            .reduce(|a, b| a || b)
-            .unwrap_or(true);
+    for (entity, planar_handle, existing) in clouds.iter() {
+        let asset_id = planar_handle.handle().id();
 
-        if not_ready {
+        let Some(gpu_planar) = gpu_planars.get(asset_id) else {
+            if existing.is_some() {
+                commands
+                    .entity(entity)
+                    .remove::<PlanarTextureBindGroup<R>>();
+            }
+            continue;
+        };
+
+        // textures are recreated whenever the source asset is added or modified
+        let stale = match existing {
+            Some(bind_group) => {
+                bind_group.asset_id != asset_id || extracted_planars.added.contains(&asset_id)
+            }
+            None => true,
+        };
+
+        if !stale {
             continue;
         }
 
-        let bind_group = texture_buffers.bind_group(
-            &render_device,
-            &gpu_images,
-            layout,
-        );
+        if let Some(load_state) = asset_server.get_load_state(asset_id)
+            && load_state.is_loading()
+        {
+            continue;
+        }
+
+        let bind_group = gpu_planar.bind_group(&render_device, layout);
 
         commands.entity(entity).insert(PlanarTextureBindGroup::<R> {
             bind_group,
+            asset_id,
             phantom: PhantomData,
         });
     }
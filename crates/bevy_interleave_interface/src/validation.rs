@@ -0,0 +1,126 @@
+use bevy::render::render_resource::{BindGroupLayoutEntry, BindingType, BufferBindingType};
+
+// a difference between a shader's global variables and a planar bind group layout
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanarBindingMismatch {
+    // the layout binds `field`, the shader declares nothing at this binding
+    Missing {
+        binding: u32,
+        field: &'static str,
+    },
+    // the shader declares a binding the layout does not provide
+    Unexpected {
+        binding: u32,
+        name: Option<String>,
+    },
+    AddressSpace {
+        binding: u32,
+        field: &'static str,
+        expected: naga::AddressSpace,
+        found: naga::AddressSpace,
+    },
+    // storage buffers must be declared as runtime-sized `array<T>`
+    NotRuntimeArray {
+        binding: u32,
+        field: &'static str,
+    },
+    Stride {
+        binding: u32,
+        field: &'static str,
+        expected: u32,
+        found: u32,
+    },
+}
+
+// compares the globals of `module` in `group` against storage layout `entries`
+// `field_names` and `strides` are indexed like `entries`
+pub fn validate_storage_bindings(
+    module: &naga::Module,
+    group: u32,
+    entries: &[BindGroupLayoutEntry],
+    field_names: &[&'static str],
+    strides: &[usize],
+) -> Vec<PlanarBindingMismatch> {
+    let mut mismatches = Vec::new();
+
+    let globals = module
+        .global_variables
+        .iter()
+        .filter_map(|(_, global)| {
+            let binding = global.binding.as_ref()?;
+            (binding.group == group).then_some((binding.binding, global))
+        })
+        .collect::<Vec<_>>();
+
+    for (idx, entry) in entries.iter().enumerate() {
+        let binding = entry.binding;
+        let field = field_names[idx];
+
+        let Some((_, global)) = globals.iter().find(|(b, _)| *b == binding) else {
+            mismatches.push(PlanarBindingMismatch::Missing { binding, field });
+            continue;
+        };
+
+        let expected = match entry.ty {
+            BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                ..
+            } => naga::AddressSpace::Storage {
+                access: if read_only {
+                    naga::StorageAccess::LOAD
+                } else {
+                    naga::StorageAccess::LOAD | naga::StorageAccess::STORE
+                },
+            },
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                ..
+            } => naga::AddressSpace::Uniform,
+            _ => naga::AddressSpace::Handle,
+        };
+
+        if global.space != expected {
+            mismatches.push(PlanarBindingMismatch::AddressSpace {
+                binding,
+                field,
+                expected,
+                found: global.space,
+            });
+            continue;
+        }
+
+        if !matches!(expected, naga::AddressSpace::Storage { .. }) {
+            continue;
+        }
+
+        match module.types[global.ty].inner {
+            naga::TypeInner::Array {
+                stride,
+                size: naga::ArraySize::Dynamic,
+                ..
+            } => {
+                let expected = strides[idx] as u32;
+                if stride != expected {
+                    mismatches.push(PlanarBindingMismatch::Stride {
+                        binding,
+                        field,
+                        expected,
+                        found: stride,
+                    });
+                }
+            }
+            _ => mismatches.push(PlanarBindingMismatch::NotRuntimeArray { binding, field }),
+        }
+    }
+
+    for (binding, global) in globals {
+        if !entries.iter().any(|entry| entry.binding == binding) {
+            mismatches.push(PlanarBindingMismatch::Unexpected {
+                binding,
+                name: global.name.clone(),
+            });
+        }
+    }
+
+    mismatches
+}
@@ -0,0 +1,68 @@
+// arrow-style validity bitmask of an `Option<T>` planar field, the first row is the lowest bit of the first word
+// note: the values of `None` rows are `T::default()`, their column stays dense
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    bevy::reflect::Reflect,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct PlanarValidity {
+    words: Vec<u32>,
+    len: usize,
+}
+
+impl PlanarValidity {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    // `false` for rows beyond `len`
+    pub fn get(&self, row: usize) -> bool {
+        row < self.len && self.words[row / 32] & (1 << (row % 32)) != 0
+    }
+
+    pub fn set(&mut self, row: usize, valid: bool) {
+        assert!(row < self.len, "row {row} out of bounds of {}", self.len);
+
+        if valid {
+            self.words[row / 32] |= 1 << (row % 32);
+        } else {
+            self.words[row / 32] &= !(1 << (row % 32));
+        }
+    }
+
+    pub fn push(&mut self, valid: bool) {
+        if self.len.is_multiple_of(32) {
+            self.words.push(0);
+        }
+
+        self.len += 1;
+        self.set(self.len - 1, valid);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|row| self.get(row))
+    }
+}
+
+impl FromIterator<bool> for PlanarValidity {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut validity = Self::default();
+        for valid in iter {
+            validity.push(valid);
+        }
+        validity
+    }
+}
@@ -1,2 +1,3 @@
+pub mod packed;
 pub mod storage;
 pub mod texture;
@@ -0,0 +1,500 @@
+use convert_case::{Case, Casing};
+use quote::quote;
+use syn::{DeriveInput, Error, Field, FieldsNamed, Generics, Ident, Result, Type};
+
+use crate::{
+    field::{
+        element_gpu_type, enum_consts, flattened_fields, gpu_fields, gpu_type, planar_fields,
+        reject_storage_only,
+    },
+    generics::{
+        is_type_param, phantom_type, wgsl_import_path, wgsl_type_features, wgsl_type_params,
+        with_planar_bounds, with_wgsl_bounds,
+    },
+    std430::{Std430Type, std430_type},
+    wgsl::{
+        WgslType, binding_name, loader_name, packed_storage_accessor, packed_storage_name,
+        page_fn_name, raw_binding_name, reject_unsupported_scalars, std430_wgsl_struct, wgsl_ident,
+        wgsl_type,
+    },
+};
+
+// a member of the interleaved std430 row
+pub struct PackedMember<'a> {
+    pub field: &'a Field,
+    pub name: String,
+    pub ty: WgslType,
+    // `None` for a type parameter, laid out through `PlanarWgslType` once instantiated
+    pub layout: Option<Std430Type>,
+}
+
+impl PackedMember<'_> {
+    // `(align, size)` of `std430_layout`
+    fn layout_tokens(&self) -> quote::__private::TokenStream {
+        match &self.layout {
+            Some(layout) => {
+                let Std430Type { align, size, .. } = layout;
+                quote! { (#align, #size) }
+            }
+            None => {
+                let ty = gpu_type(self.field);
+                quote! {
+                    (
+                        <#ty as bevy_interleave::interface::PlanarWgslType>::WGSL_ALIGN,
+                        std::mem::size_of::<#ty>(),
+                    )
+                }
+            }
+        }
+    }
+
+    // `Std430Column` reading the planar `source.#field` column, `idx` within `STD430_OFFSETS`
+    fn column_tokens(&self, idx: usize) -> quote::__private::TokenStream {
+        let name = self.field.ident.as_ref().unwrap();
+        let field_type = element_gpu_type(self.field);
+
+        let segments = match &self.layout {
+            Some(layout) => layout
+                .segments
+                .iter()
+                .map(|segment| segment.tokens())
+                .collect::<Vec<_>>(),
+            // note: the wgsl stride of a `PlanarWgslType` is its `size_of`
+            None => vec![quote! {
+                bevy_interleave::interface::std430::Std430Segment {
+                    host: 0,
+                    offset: 0,
+                    len: std::mem::size_of::<#field_type>(),
+                    widen_bool: false,
+                }
+            }],
+        };
+
+        quote! {
+            bevy_interleave::interface::std430::Std430Column {
+                bytes: bytemuck::cast_slice(source.#name.as_slice()),
+                host_size: std::mem::size_of::<#field_type>(),
+                offset: Self::STD430_OFFSETS[#idx],
+                segments: &[
+                    #(#segments),*
+                ],
+            }
+        }
+    }
+}
+
+fn packed_member<'a>(field: &'a Field, generics: &Generics) -> Result<PackedMember<'a>> {
+    let ty: Type = gpu_type(field);
+
+    let layout = if is_type_param(&ty, generics) {
+        None
+    } else {
+        Some(std430_type(&ty).ok_or_else(|| {
+            Error::new_spanned(
+                &field.ty,
+                "no std430 layout for this type, packed storage supports 32-bit scalars, bool, arrays, glam vectors and matrices and type parameters",
+            )
+        })?)
+    };
+
+    Ok(PackedMember {
+        field,
+        name: field.ident.as_ref().unwrap().to_string(),
+        ty: wgsl_type(&ty),
+        layout,
+    })
+}
+
+pub fn packed_storage_bindings(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
+    let name = &input.ident;
+    let generics = with_wgsl_bounds(&with_planar_bounds(&input.generics));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let phantom_type = phantom_type(name, &input.generics);
+
+    let planar_name = Ident::new(&format!("Planar{name}"), name.span());
+    let gpu_planar_name = Ident::new(&format!("PackedStorage{name}"), name.span());
+    let planar_handle_name = Ident::new(&format!("Planar{name}Handle"), name.span());
+    let marker_name = Ident::new(&format!("{name}Packed"), name.span());
+
+    let fields_struct = &planar_fields(input)?;
+    reject_storage_only(fields_struct, "PackedStorageBindings")?;
+    reject_unsupported_scalars(fields_struct)?;
+
+    // note: nested structs have no std430 layout within the packed row
+    if let Some(flattened) = flattened_fields(input)?.first() {
+        return Err(Error::new_spanned(
+            flattened.field,
+            "`#[planar(flatten)]` fields are not supported by `PackedStorageBindings`, use `StorageBindings`",
+        ));
+    }
+
+    let members = gpu_fields(fields_struct)
+        .map(|(_, field)| packed_member(field, &input.generics))
+        .collect::<Result<Vec<_>>>()?;
+
+    let member_count = members.len();
+    let member_layouts = members.iter().map(PackedMember::layout_tokens);
+    let columns = members
+        .iter()
+        .enumerate()
+        .map(|(idx, member)| member.column_tokens(idx));
+
+    let allocation_label = format!(
+        "packed_storage_{}_allocation",
+        name.to_string().to_case(Case::Snake)
+    );
+
+    let bind_group = generate_bind_group_method(name);
+    let bind_group_layout = generate_bind_group_layout_method(name);
+    let wgsl_source = generate_wgsl_source_method(name, &generics, fields_struct, &members);
+
+    let expanded = quote! {
+        #[derive(Debug, Clone)]
+        pub struct #gpu_planar_name #generics #where_clause {
+            // interleaved std430 rows, one buffer per page
+            pub packed: Vec<bevy_interleave::interface::paging::PlanarColumnPage>,
+            pub count: usize,
+            pub draw_indirect_buffer: bevy::render::render_resource::Buffer,
+            pub page_metadata: bevy::render::render_resource::Buffer,
+            pub phantom: #phantom_type,
+        }
+
+        impl #impl_generics #gpu_planar_name #ty_generics #where_clause {
+            // note: evaluated per instantiation, type parameters are laid out through `PlanarWgslType`
+            const STD430_LAYOUT: ([usize; #member_count], usize) =
+                bevy_interleave::interface::std430::std430_layout([#(#member_layouts),*]);
+
+            // array stride of the wgsl struct, not `size_of`
+            pub const STD430_STRIDE: usize = Self::STD430_LAYOUT.1;
+            pub const STD430_OFFSETS: &'static [usize] = &Self::STD430_LAYOUT.0;
+
+            pub fn std430_bytes(source: &#planar_name #ty_generics) -> Vec<u8> {
+                bevy_interleave::interface::std430::interleave_std430(
+                    source.len(),
+                    Self::STD430_STRIDE,
+                    &[
+                        #(#columns),*
+                    ],
+                )
+            }
+        }
+
+        impl #impl_generics bevy::render::render_asset::RenderAsset for #gpu_planar_name #ty_generics #where_clause {
+            type SourceAsset = #planar_name #ty_generics;
+            type Param = (
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderDevice>,
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderQueue>,
+                Option<bevy::ecs::system::lifetimeless::SRes<bevy_interleave::interface::storage::PlanarStorageLayouts<#marker_name #ty_generics>>>,
+            );
+
+            fn prepare_asset(
+                source: Self::SourceAsset,
+                _: AssetId<Self::SourceAsset>,
+                (render_device, render_queue, layouts): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+                previous_asset: Option<&Self>,
+            ) -> Result<Self, bevy::render::render_asset::PrepareAssetError<Self::SourceAsset>> {
+                // note: missing when the storage bindings exceed the device limits
+                let Some(layouts) = layouts else {
+                    return Err(bevy::render::render_asset::PrepareAssetError::RetryNextUpdate(source));
+                };
+
+                Ok(Self::prepare_storage(
+                    &source,
+                    render_device,
+                    render_queue,
+                    &layouts.partition.sharing,
+                    &layouts.paging,
+                    layouts.allocation,
+                    previous_asset,
+                ))
+            }
+
+            fn asset_usage(_: &Self::SourceAsset) -> bevy::asset::RenderAssetUsages {
+                bevy::asset::RenderAssetUsages::default()
+            }
+        }
+
+        impl #impl_generics GpuPlanar for #gpu_planar_name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
+            type PlanarType = #planar_name #ty_generics;
+
+            fn len(&self) -> usize {
+                self.count
+            }
+        }
+
+        impl #impl_generics GpuPlanarStorage for #gpu_planar_name #ty_generics #where_clause {
+            fn draw_indirect_buffer(&self) -> &bevy::render::render_resource::Buffer {
+                return &self.draw_indirect_buffer;
+            }
+
+            fn prepare_storage(
+                source: &Self::PlanarType,
+                render_device: &bevy::render::renderer::RenderDevice,
+                render_queue: &bevy::render::renderer::RenderQueue,
+                sharing: &bevy_interleave::interface::partition::PlanarSharing,
+                paging: &bevy_interleave::interface::paging::PlanarPaging,
+                allocation: bevy_interleave::interface::allocation::PlanarAllocation,
+                previous_asset: Option<&Self>,
+            ) -> Self {
+                let count = source.len();
+
+                // note: unchanged lengths reuse the previous buffers, rows dirty in any column are written
+                let previous_asset = previous_asset.filter(|previous_asset| previous_asset.count == count);
+                let rows = if source.dirty_ranges.is_empty() {
+                    std::iter::once(0..count).collect()
+                } else {
+                    source.dirty_ranges.rows()
+                };
+
+                let draw_indirect_buffer = match previous_asset {
+                    Some(previous_asset) => previous_asset.draw_indirect_buffer.clone(),
+                    None => render_device.create_buffer_with_data(&bevy::render::render_resource::BufferInitDescriptor {
+                        label: Some("draw indirect buffer"),
+                        contents: wgpu::util::DrawIndirectArgs {  // TODO: reexport this type
+                            vertex_count: 4,
+                            instance_count: count as u32,
+                            first_vertex: 0,
+                            first_instance: 0,
+                        }.as_bytes(),
+                        usage: bevy::render::render_resource::BufferUsages::INDIRECT
+                             | bevy::render::render_resource::BufferUsages::COPY_DST
+                             | bevy::render::render_resource::BufferUsages::STORAGE
+                             | bevy::render::render_resource::BufferUsages::COPY_SRC,
+                    }),
+                };
+
+                paging.warn_truncated(std::any::type_name::<Self::SourceAsset>(), count);
+
+                let page_metadata = bevy_interleave::interface::paging::prepare_page_metadata(
+                    render_device,
+                    render_queue,
+                    paging,
+                    count,
+                    previous_asset.map(|previous_asset| &previous_asset.page_metadata),
+                );
+
+                let bytes = Self::std430_bytes(source);
+                let previous_columns = previous_asset.map(|previous_asset| [previous_asset.packed.as_slice()]);
+                let [packed] = <[_; 1]>::try_from(
+                    bevy_interleave::interface::allocation::prepare_columns(
+                        render_device,
+                        render_queue,
+                        #allocation_label,
+                        &[
+                            bevy_interleave::interface::allocation::PlanarColumnUpload {
+                                label: "packed_buffer",
+                                bytes: &bytes,
+                                stride: Self::STD430_STRIDE,
+                                rows: &rows,
+                                bit_packed: false,
+                            },
+                        ],
+                        sharing,
+                        paging,
+                        allocation,
+                        previous_columns.as_ref().map(|columns| columns.as_slice()),
+                    ),
+                ).unwrap();
+
+                Self {
+                    count,
+                    draw_indirect_buffer,
+                    page_metadata,
+                    packed,
+                    phantom: std::marker::PhantomData,
+                }
+            }
+
+            fn column_pages(&self) -> Vec<&[bevy_interleave::interface::paging::PlanarColumnPage]> {
+                vec![self.packed.as_slice()]
+            }
+
+            #bind_group
+            #bind_group_layout
+            #wgsl_source
+        }
+
+        // note: `R` of the storage plugin, `#name` keeps `PlanarStorage` free for `StorageBindings`
+        #[derive(bevy::reflect::Reflect)]
+        pub struct #marker_name #generics (#[reflect(ignore)] #phantom_type) #where_clause;
+
+        // note: derived `Clone` and `Default` would require them of the type parameters
+        impl #impl_generics Clone for #marker_name #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                Self(std::marker::PhantomData)
+            }
+        }
+
+        impl #impl_generics Default for #marker_name #ty_generics #where_clause {
+            fn default() -> Self {
+                Self(std::marker::PhantomData)
+            }
+        }
+
+        impl #impl_generics bevy_interleave::interface::PlanarSync for #marker_name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
+            type PlanarType = #planar_name #ty_generics;
+            type PlanarTypeHandle = #planar_handle_name #ty_generics;
+        }
+
+        impl #impl_generics PlanarStorage for #marker_name #ty_generics #where_clause {
+            type GpuStorageType = #gpu_planar_name #ty_generics;
+        }
+    };
+
+    Ok(expanded)
+}
+
+pub fn generate_bind_group_method(struct_name: &Ident) -> quote::__private::TokenStream {
+    let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
+    let bind_group_name = format!("packed_storage_{struct_name_snake}_bind_group");
+
+    quote! {
+        fn bind_groups(
+            &self,
+            render_device: &bevy::render::renderer::RenderDevice,
+            layouts: &[bevy::render::render_resource::BindGroupLayout],
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+        ) -> Vec<bevy::render::render_resource::BindGroup> {
+            bevy_interleave::interface::paging::paged_bind_groups(
+                render_device,
+                #bind_group_name,
+                layouts,
+                partition,
+                &self.column_pages(),
+                &self.page_metadata,
+            )
+        }
+    }
+}
+
+pub fn generate_bind_group_layout_method(struct_name: &Ident) -> quote::__private::TokenStream {
+    let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
+    let bind_group_layout_name = format!("packed_storage_{struct_name_snake}_bind_group_layout");
+
+    quote! {
+        fn bind_group_layout_entries(
+            read_only: bool,
+        ) -> Vec<bevy::render::render_resource::BindGroupLayoutEntry> {
+            // note: writable storage is compute only by default, fragment writes need `DownlevelFlags::FRAGMENT_WRITABLE_STORAGE`
+            let visibility = if read_only {
+                bevy::render::render_resource::ShaderStages::VERTEX_FRAGMENT
+                    | bevy::render::render_resource::ShaderStages::COMPUTE
+            } else {
+                bevy::render::render_resource::ShaderStages::COMPUTE
+            };
+
+            vec![
+                bevy::render::render_resource::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: bevy::render::render_resource::BindingType::Buffer {
+                        ty: bevy::render::render_resource::BufferBindingType::Storage { read_only },
+                        has_dynamic_offset: false,
+                        min_binding_size: bevy::render::render_resource::BufferSize::new(Self::STD430_STRIDE as u64),
+                    },
+                    count: None,
+                },
+            ]
+        }
+
+        fn binding_names() -> &'static [&'static str] {
+            &["packed"]
+        }
+
+        fn bind_group_layouts(
+            render_device: &bevy::render::renderer::RenderDevice,
+            read_only: bool,
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+            paging: &bevy_interleave::interface::paging::PlanarPaging,
+        ) -> Vec<bevy::render::render_resource::BindGroupLayout> {
+            bevy_interleave::interface::paging::paged_bind_group_layouts(
+                render_device,
+                #bind_group_layout_name,
+                Self::bind_group_layout_entries(read_only),
+                partition,
+                paging,
+            )
+        }
+    }
+}
+
+pub fn generate_wgsl_source_method(
+    struct_name: &Ident,
+    generics: &Generics,
+    fields_named: &FieldsNamed,
+    members: &[PackedMember],
+) -> quote::__private::TokenStream {
+    let wgsl_members = || {
+        members
+            .iter()
+            .map(|member| (member.name.as_str(), &member.ty))
+    };
+
+    let binding = binding_name(struct_name, "packed");
+    let page_prefix = format!("{}_page", raw_binding_name(struct_name, "packed"));
+    let loader = loader_name(struct_name, "packed");
+    let storage = packed_storage_name(struct_name);
+    let storage_struct = std430_wgsl_struct(&storage, wgsl_members());
+    let wgsl_type_params = wgsl_type_params(generics);
+    let enum_consts = enum_consts(gpu_fields(fields_named).map(|(_, field)| field));
+
+    let metadata_struct = wgsl_ident(&format!("{struct_name}Pages"));
+    let metadata_binding = binding_name(struct_name, "paging");
+    let page_fn = page_fn_name(struct_name);
+
+    let accessor = packed_storage_accessor(struct_name, wgsl_members(), &loader);
+    let import_path = wgsl_import_path(struct_name, generics);
+    let type_features = wgsl_type_features(generics);
+
+    quote! {
+        fn wgsl_columns() -> Vec<bevy_interleave::interface::paging::PlanarWgslColumn> {
+            vec![
+                bevy_interleave::interface::paging::PlanarWgslColumn {
+                    binding: #binding.to_string(),
+                    page_prefix: #page_prefix.to_string(),
+                    loader: #loader.to_string(),
+                    storage: #storage.to_string(),
+                    read_only: None,
+                    bit_packed: false,
+                    shareable: true,
+                },
+            ]
+        }
+
+        fn wgsl_column_types() -> String {
+            bevy_interleave::interface::substitute_wgsl_types(#storage_struct, #wgsl_type_params)
+        }
+
+        fn wgsl_partitioned_source(
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+            paging: &bevy_interleave::interface::paging::PlanarPaging,
+            read_only: bool,
+        ) -> String {
+            let source = bevy_interleave::interface::paging::wgsl_paged_source(
+                &format!("{}{}\n{}", #enum_consts, Self::PackedType::wgsl_struct(), Self::wgsl_column_types()),
+                &Self::wgsl_columns(),
+                &bevy_interleave::interface::paging::PlanarWgslPages {
+                    metadata_struct: #metadata_struct,
+                    metadata_binding: #metadata_binding,
+                    page_fn: #page_fn,
+                },
+                #accessor,
+                partition,
+                paging,
+                read_only,
+            );
+
+            bevy_interleave::interface::substitute_wgsl_types(&source, #wgsl_type_params)
+        }
+
+        fn wgsl_import_path() -> String {
+            #import_path
+        }
+
+        fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+            #type_features
+        }
+    }
+}
@@ -1,98 +1,323 @@
 use convert_case::{Case, Casing};
-use quote::quote;
-use syn::{Data, DeriveInput, Error, Fields, FieldsNamed, Ident, Result};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{BinOp, DeriveInput, Error, Expr, Field, FieldsNamed, Generics, Ident, LitInt, Result};
+
+use crate::{
+    field::{
+        FlattenedField, element_gpu_type, enum_consts, flattened_fields, gpu_fields, gpu_type,
+        is_gpu_only, is_list, is_optional, offsets_name, planar_fields, validity_name,
+        wgsl_field_type,
+    },
+    generics::{
+        phantom_type, wgsl_import_path, wgsl_type_features, wgsl_type_params, with_planar_bounds,
+        with_wgsl_bounds,
+    },
+    std430::{Std430Struct, padded_column, std430_columns, std430_struct, std430_type},
+    wgsl::{
+        WgslType, accessor, binding_name, bool_accessor, field_accessor, list_range_accessor,
+        list_range_accessor_name, loader_name, page_fn_name, raw_binding_name,
+        reject_unsupported_scalars, std430_wgsl_struct, stream_struct_name, validity_accessor,
+        wgsl_ident, wgsl_type,
+    },
+};
 
 pub fn storage_bindings(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
     let name = &input.ident;
+    let generics = with_wgsl_bounds(&with_planar_bounds(&input.generics));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let phantom_type = phantom_type(name, &input.generics);
 
     let planar_name = Ident::new(&format!("Planar{name}"), name.span());
     let gpu_planar_name = Ident::new(&format!("PlanarStorage{name}"), name.span());
-    let planar_handle_name = Ident::new(&format!("Planar{name}Handle"), name.span());
-
-    let fields_struct = if let Data::Struct(ref data_struct) = input.data {
-        match data_struct.fields {
-            Fields::Named(ref fields) => fields,
-            _ => return Err(Error::new_spanned(input, "Unsupported struct type")),
-        }
-    } else {
-        return Err(Error::new_spanned(
-            input,
-            "Planar macro only supports structs",
-        ));
-    };
 
-    let field_names = fields_struct
-        .named
-        .iter()
-        .map(|f| f.ident.as_ref().unwrap());
-    let field_types = fields_struct.named.iter().map(|_| {
-        quote! { bevy::render::render_resource::Buffer }
-    });
+    let fields_struct = &planar_fields(input)?;
+    reject_unsupported_scalars(fields_struct)?;
 
-    let bind_group = generate_bind_group_method(name, fields_struct);
-    let bind_group_layout = generate_bind_group_layout_method(name, fields_struct);
+    let columns = storage_columns(fields_struct)?;
+    let flattened = &flattened_fields(input)?;
 
-    let buffers = field_names.clone().map(|name| {
-        let buffer_name_string = format!("{name}_buffer");
+    for flattened in flattened {
+        if parse_storage_attrs(flattened.field)?.is_set()
+            || parse_stream(flattened.field)?.is_some()
+        {
+            return Err(Error::new_spanned(
+                flattened.field,
+                "storage attributes of a flattened field go on the fields of its struct",
+            ));
+        }
+    }
 
+    // the gpu struct of each `#[planar(flatten)]` field, uploaded with this struct's paging
+    let flattened_columns = flattened.iter().map(|flattened| &flattened.column);
+    let flattened_types = flattened.iter().map(|flattened| {
+        let ty = flattened.ty;
+        quote! { <#ty as bevy_interleave::interface::PlanarStorage>::GpuStorageType }
+    });
+    let flattened_uploads = flattened.iter().map(|flattened| {
+        let FlattenedField { column, ty, .. } = flattened;
         quote! {
-            let #name = render_device.create_buffer_with_data(
-                &bevy::render::render_resource::BufferInitDescriptor {
-                    label: Some(#buffer_name_string),
-                    contents: bytemuck::cast_slice(source.#name.as_slice()),
-                    usage: bevy::render::render_resource::BufferUsages::COPY_DST
-                         | bevy::render::render_resource::BufferUsages::STORAGE,
-                }
+            let #column = <<#ty as bevy_interleave::interface::PlanarStorage>::GpuStorageType as GpuPlanarStorage>::prepare_storage(
+                &source.#column,
+                render_device,
+                render_queue,
+                &Default::default(),
+                paging,
+                allocation,
+                previous_asset.map(|previous_asset| &previous_asset.#column),
             );
         }
     });
+    let flattened_names = flattened.iter().map(|flattened| &flattened.column);
+
+    let column_names = columns.iter().map(|column| &column.member);
+    let column_types = columns.iter().map(|_| {
+        quote! { Vec<bevy_interleave::interface::paging::PlanarColumnPage> }
+    });
+
+    let bind_group = generate_bind_group_method(name, &columns, flattened);
+    let bind_group_layout = generate_bind_group_layout_method(name, &columns, flattened);
+    let wgsl_source =
+        generate_wgsl_source_method(name, &generics, fields_struct, &columns, flattened)?;
+
+    // std430 rows of streams and padded fields and their dirty rows, borrowed by the uploads
+    let std430_bytes = columns.iter().filter_map(|column| {
+        let layout = column.layout.as_ref()?;
+        if column.kind == StorageColumnKind::Scratch {
+            return None;
+        }
+
+        let member = &column.member;
+        let bytes = format_ident!("{member}_bytes");
+        let rows = format_ident!("{member}_rows");
+
+        let stride = layout.size;
+        let indices = column.fields.iter().map(|(idx, _)| idx);
+        let len = match column.kind {
+            StorageColumnKind::Values => quote! { source.#member.len() },
+            _ => quote! { count },
+        };
+        // note: the dirty values of `Vec<T>` fields are found through their offsets, see `list_rows`
+        let rows = (column.kind != StorageColumnKind::Values).then(|| {
+            quote! {
+                let #rows = if source.dirty_ranges.is_empty() {
+                    all_rows.to_vec()
+                } else {
+                    source.dirty_ranges.union(&[#(#indices),*])
+                };
+            }
+        });
+        let std430_columns = std430_columns(
+            column
+                .fields
+                .iter()
+                .map(|(_, field)| *field)
+                .zip(&layout.members),
+        );
 
-    let buffer_names = field_names.clone().map(|name| {
-        quote! { #name }
+        Some(quote! {
+            let #bytes = bevy_interleave::interface::std430::interleave_std430(
+                #len,
+                #stride,
+                &[
+                    #(#std430_columns),*
+                ],
+            );
+            #rows
+        })
     });
 
+    // zeroed rows of `gpu_only` fields, reused buffers keep their contents
+    let scratch_bytes = columns
+        .iter()
+        .filter(|column| column.kind == StorageColumnKind::Scratch)
+        .map(|column| {
+            let member = &column.member;
+            let bytes = format_ident!("{member}_bytes");
+            let stride = scratch_stride(column);
+
+            quote! {
+                let #bytes = match previous_asset {
+                    Some(_) => Vec::new(),
+                    None => vec![0u8; count * #stride],
+                };
+            }
+        });
+
+    // values of the dirty rows of `Vec<T>` fields
+    let list_rows = columns
+        .iter()
+        .filter(|column| column.kind == StorageColumnKind::Values)
+        .map(|column| {
+            let member = &column.member;
+            let rows = format_ident!("{member}_rows");
+            let (idx, field) = column.fields[0];
+            let offsets = offsets_name(field);
+
+            quote! {
+                let #rows = bevy_interleave::interface::list::list_value_rows(
+                    &source.#offsets,
+                    if source.dirty_ranges.is_empty() {
+                        all_rows.as_slice()
+                    } else {
+                        source.dirty_ranges.column(#idx)
+                    },
+                );
+            }
+        });
+
+    // `u32` words of bit-packed bool fields and validity bitmasks and their dirty words
+    let bit_words = columns
+        .iter()
+        .filter(|column| column.attrs.bit_packed)
+        .map(|column| {
+            let member = &column.member;
+            let bytes = format_ident!("{member}_bytes");
+            let rows = format_ident!("{member}_rows");
+            let (idx, field) = column.fields[0];
+            let name = field.ident.as_ref().unwrap();
+
+            let words = if column.kind == StorageColumnKind::Validity {
+                quote! { paging.pack_validity(&source.#member) }
+            } else {
+                quote! { paging.pack_bits(&source.#name) }
+            };
+
+            quote! {
+                let #bytes = #words;
+                let #rows = paging.bit_packed_rows(if source.dirty_ranges.is_empty() {
+                    all_rows.as_slice()
+                } else {
+                    source.dirty_ranges.column(#idx)
+                });
+            }
+        });
+
+    let uploads = columns.iter().map(|column| {
+        let member = &column.member;
+        let buffer_name_string = format!("{member}_buffer");
+        let bit_packed = column.attrs.bit_packed;
+
+        let (bytes, stride, rows) = match &column.layout {
+            _ if column.kind == StorageColumnKind::Scratch => {
+                let bytes = format_ident!("{member}_bytes");
+                let stride = scratch_stride(column);
+
+                (quote! { &#bytes }, stride, quote! { &[] })
+            }
+            None if bit_packed => {
+                let bytes = format_ident!("{member}_bytes");
+                let rows = format_ident!("{member}_rows");
+
+                (
+                    quote! { bytemuck::cast_slice(&#bytes) },
+                    quote! { 4 },
+                    quote! { &#rows },
+                )
+            }
+            None if column.kind == StorageColumnKind::Values => {
+                let field_type = element_gpu_type(column.fields[0].1);
+                let rows = format_ident!("{member}_rows");
+
+                (
+                    quote! { bytemuck::cast_slice(source.#member.as_slice()) },
+                    quote! { std::mem::size_of::<#field_type>() },
+                    quote! { &#rows },
+                )
+            }
+            None => {
+                let (idx, field) = column.fields[0];
+                let field_type = gpu_type(field);
+
+                (
+                    quote! { bytemuck::cast_slice(source.#member.as_slice()) },
+                    quote! { std::mem::size_of::<#field_type>() },
+                    quote! {
+                        if source.dirty_ranges.is_empty() {
+                            all_rows.as_slice()
+                        } else {
+                            source.dirty_ranges.column(#idx)
+                        }
+                    },
+                )
+            }
+            Some(layout) => {
+                let bytes = format_ident!("{member}_bytes");
+                let rows = format_ident!("{member}_rows");
+                let stride = layout.size;
+
+                (quote! { &#bytes }, quote! { #stride }, quote! { &#rows })
+            }
+        };
+
+        quote! {
+            bevy_interleave::interface::allocation::PlanarColumnUpload {
+                label: #buffer_name_string,
+                bytes: #bytes,
+                stride: #stride,
+                rows: #rows,
+                bit_packed: #bit_packed,
+            }
+        }
+    });
+
+    let column_count = columns.len();
+    let previous_columns = columns.iter().map(|column| &column.member);
+    let allocation_label = format!(
+        "storage_{}_allocation",
+        name.to_string().to_case(Case::Snake)
+    );
+
+    let buffer_names = columns.iter().map(|column| &column.member);
+    let destructured_names = buffer_names.clone();
+    let list_values = columns
+        .iter()
+        .filter(|column| column.kind == StorageColumnKind::Values)
+        .map(|column| &column.member);
+
     let expanded = quote! {
         #[derive(Debug, Clone)]
-        pub struct #gpu_planar_name {
-            #(pub #field_names: #field_types,)*
+        pub struct #gpu_planar_name #generics #where_clause {
+            // one binding per page of each field or `#[stream(n)]`
+            #(pub #column_names: #column_types,)*
+            // columns of `#[planar(flatten)]` fields, bound after this struct's columns
+            #(pub #flattened_columns: #flattened_types,)*
             pub count: usize,
+            // lengths of the flat values of `Vec<T>` fields
+            pub value_counts: Vec<usize>,
             pub draw_indirect_buffer: bevy::render::render_resource::Buffer,
+            pub page_metadata: bevy::render::render_resource::Buffer,
+            pub phantom: #phantom_type,
         }
 
-        impl bevy::render::render_asset::RenderAsset for #gpu_planar_name {
-            type SourceAsset = #planar_name;
-            type Param = bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderDevice>;
+        impl #impl_generics bevy::render::render_asset::RenderAsset for #gpu_planar_name #ty_generics #where_clause {
+            type SourceAsset = #planar_name #ty_generics;
+            type Param = (
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderDevice>,
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderQueue>,
+                Option<bevy::ecs::system::lifetimeless::SRes<bevy_interleave::interface::storage::PlanarStorageLayouts<#name #ty_generics>>>,
+            );
 
             fn prepare_asset(
                 source: Self::SourceAsset,
                 _: AssetId<Self::SourceAsset>,
-                render_device: &mut bevy::ecs::system::SystemParamItem<Self::Param>,
-                _: Option<&Self>,
+                (render_device, render_queue, layouts): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+                previous_asset: Option<&Self>,
             ) -> Result<Self, bevy::render::render_asset::PrepareAssetError<Self::SourceAsset>> {
-                let count = source.len();
+                // note: missing when the storage bindings exceed the device limits
+                let Some(layouts) = layouts else {
+                    return Err(bevy::render::render_asset::PrepareAssetError::RetryNextUpdate(source));
+                };
 
-                let draw_indirect_buffer = render_device.create_buffer_with_data(&bevy::render::render_resource::BufferInitDescriptor {
-                    label: Some("draw indirect buffer"),
-                    contents: wgpu::util::DrawIndirectArgs {  // TODO: reexport this type
-                        vertex_count: 4,
-                        instance_count: count as u32,
-                        first_vertex: 0,
-                        first_instance: 0,
-                    }.as_bytes(),
-                    usage: bevy::render::render_resource::BufferUsages::INDIRECT
-                         | bevy::render::render_resource::BufferUsages::COPY_DST
-                         | bevy::render::render_resource::BufferUsages::STORAGE
-                         | bevy::render::render_resource::BufferUsages::COPY_SRC,
-                });
-
-                #(#buffers)*
-
-                Ok(Self {
-                    count,
-                    draw_indirect_buffer,
-
-                    #(#buffer_names),*
-                })
+                Ok(Self::prepare_storage(
+                    &source,
+                    render_device,
+                    render_queue,
+                    &layouts.partition.sharing,
+                    &layouts.paging,
+                    layouts.allocation,
+                    previous_asset,
+                ))
             }
 
             fn asset_usage(_: &Self::SourceAsset) -> bevy::asset::RenderAssetUsages {
@@ -100,70 +325,516 @@ pub fn storage_bindings(input: &DeriveInput) -> Result<quote::__private::TokenSt
             }
         }
 
-        impl GpuPlanar for #gpu_planar_name {
-            type PackedType = #name;
-            type PlanarType = #planar_name;
+        impl #impl_generics GpuPlanar for #gpu_planar_name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
+            type PlanarType = #planar_name #ty_generics;
 
             fn len(&self) -> usize {
                 self.count
             }
         }
 
-        impl GpuPlanarStorage for #gpu_planar_name {
+        impl #impl_generics GpuPlanarStorage for #gpu_planar_name #ty_generics #where_clause {
             fn draw_indirect_buffer(&self) -> &bevy::render::render_resource::Buffer {
                 return &self.draw_indirect_buffer;
             }
 
+            fn prepare_storage(
+                source: &Self::PlanarType,
+                render_device: &bevy::render::renderer::RenderDevice,
+                render_queue: &bevy::render::renderer::RenderQueue,
+                sharing: &bevy_interleave::interface::partition::PlanarSharing,
+                paging: &bevy_interleave::interface::paging::PlanarPaging,
+                allocation: bevy_interleave::interface::allocation::PlanarAllocation,
+                previous_asset: Option<&Self>,
+            ) -> Self {
+                let count = source.len();
+
+                // note: rows beyond `paging.capacity()` are dropped with a warning, `count` in the page metadata is clamped
+                // note: unchanged lengths reuse the previous buffers, only dirty rows are written
+                let value_counts = vec![#(source.#list_values.len()),*];
+                let previous_asset = previous_asset.filter(|previous_asset| {
+                    previous_asset.count == count && previous_asset.value_counts == value_counts
+                });
+                let all_rows = [0..count];
+
+                let draw_indirect_buffer = match previous_asset {
+                    Some(previous_asset) => previous_asset.draw_indirect_buffer.clone(),
+                    None => render_device.create_buffer_with_data(&bevy::render::render_resource::BufferInitDescriptor {
+                        label: Some("draw indirect buffer"),
+                        contents: wgpu::util::DrawIndirectArgs {  // TODO: reexport this type
+                            vertex_count: 4,
+                            instance_count: count as u32,
+                            first_vertex: 0,
+                            first_instance: 0,
+                        }.as_bytes(),
+                        usage: bevy::render::render_resource::BufferUsages::INDIRECT
+                             | bevy::render::render_resource::BufferUsages::COPY_DST
+                             | bevy::render::render_resource::BufferUsages::STORAGE
+                             | bevy::render::render_resource::BufferUsages::COPY_SRC,
+                    }),
+                };
+
+                paging.warn_truncated(std::any::type_name::<Self::SourceAsset>(), count);
+
+                let page_metadata = bevy_interleave::interface::paging::prepare_page_metadata(
+                    render_device,
+                    render_queue,
+                    paging,
+                    count,
+                    previous_asset.map(|previous_asset| &previous_asset.page_metadata),
+                );
+
+                #(#scratch_bytes)*
+                #(#list_rows)*
+                #(#std430_bytes)*
+                #(#bit_words)*
+
+                let previous_columns = previous_asset.map(|previous_asset| [
+                    #(previous_asset.#previous_columns.as_slice()),*
+                ]);
+                let [#(#destructured_names),*] = <[_; #column_count]>::try_from(
+                    bevy_interleave::interface::allocation::prepare_columns(
+                        render_device,
+                        render_queue,
+                        #allocation_label,
+                        &[
+                            #(#uploads),*
+                        ],
+                        sharing,
+                        paging,
+                        allocation,
+                        previous_columns.as_ref().map(|columns| columns.as_slice()),
+                    ),
+                ).unwrap();
+
+                #(#flattened_uploads)*
+
+                Self {
+                    count,
+                    value_counts,
+                    draw_indirect_buffer,
+                    page_metadata,
+                    phantom: std::marker::PhantomData,
+
+                    #(#buffer_names,)*
+                    #(#flattened_names),*
+                }
+            }
+
             #bind_group
             #bind_group_layout
+            #wgsl_source
         }
 
-        impl PlanarSync for #name {
-            type PackedType = #name;
-            type PlanarType = #planar_name;
-            type PlanarTypeHandle = #planar_handle_name;
-            type GpuPlanarType = #gpu_planar_name;
+        impl #impl_generics PlanarStorage for #name #ty_generics #where_clause {
+            type GpuStorageType = #gpu_planar_name #ty_generics;
         }
     };
 
     Ok(expanded)
 }
 
+// element size of a `gpu_only` column, e.g. padded to 16 bytes for `Vec3`
+fn scratch_stride(column: &StorageColumn) -> TokenStream {
+    match &column.layout {
+        Some(layout) => {
+            let stride = layout.size;
+            quote! { #stride }
+        }
+        None => {
+            let field_type = gpu_type(column.fields[0].1);
+            quote! { std::mem::size_of::<#field_type>() }
+        }
+    }
+}
+
+// one storage binding, a single planar field or a stream
+pub struct StorageColumn<'a> {
+    // member of the generated gpu struct, e.g. `position` or `stream0`
+    pub member: Ident,
+    // field indices within the packed struct
+    pub fields: Vec<(usize, &'a Field)>,
+    // fields sharing a `#[stream(n)]` are interleaved into one buffer
+    pub stream: Option<u32>,
+    // std430 rows of a stream or a padded field, `None` for columns uploaded as is
+    pub layout: Option<Std430Struct>,
+    pub attrs: StorageFieldAttrs,
+    pub kind: StorageColumnKind,
+}
+
+// what a column holds of its fields
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StorageColumnKind {
+    // one element per row, a field or a stream
+    Rows,
+    // the bit-packed validity bitmask of an `Option<T>` field, bound after its values
+    Validity,
+    // the flat values of a `Vec<T>` field, paged by value index
+    Values,
+    // the `u32` end offsets of a `Vec<T>` field, bound after its values
+    Offsets,
+    // rows of a `gpu_only` field, zeroed when allocated and never written by the CPU
+    Scratch,
+}
+
+impl StorageColumn<'_> {
+    // e.g. `position` or `stream0`, reported by validation and prefixed for wgsl names
+    pub fn name(&self) -> String {
+        self.member.to_string()
+    }
+}
+
+// `#[storage(read_write, visibility = compute, binding = 5)]`
+pub struct StorageFieldAttrs {
+    // `None` follows the `read_only` argument of the generated methods
+    pub read_only: Option<bool>,
+    pub visibility: Option<TokenStream>,
+    pub binding: u32,
+    // `#[storage(bit_packed)]` bool fields are packed 32 rows per `u32` word instead of promoted to `u32`
+    pub bit_packed: bool,
+}
+
+// note: bindings without an explicit index continue from the previous binding, like enum discriminants
+pub fn storage_columns(fields_named: &FieldsNamed) -> Result<Vec<StorageColumn<'_>>> {
+    let mut columns: Vec<StorageColumn> = Vec::new();
+    let mut bindings: Vec<(StorageAttrs, &Field)> = Vec::new();
+
+    for (idx, field) in gpu_fields(fields_named) {
+        let attrs = parse_storage_attrs(field)?;
+        let stream = parse_stream(field)?;
+
+        if is_gpu_only(field) && (stream.is_some() || attrs.bit_packed) {
+            return Err(Error::new_spanned(
+                field,
+                "`gpu_only` fields can not be bit-packed or part of a stream",
+            ));
+        }
+
+        if stream.is_some() && (is_optional(field) || is_list(field)) {
+            return Err(Error::new_spanned(
+                field,
+                "`Option` and `Vec` fields can not be part of a stream",
+            ));
+        }
+
+        let existing = stream.and_then(|stream| {
+            columns
+                .iter()
+                .position(|column| column.stream == Some(stream))
+        });
+
+        match existing {
+            Some(column) => {
+                if attrs.is_set() {
+                    return Err(Error::new_spanned(
+                        field,
+                        "storage attributes of a stream go on its first field",
+                    ));
+                }
+                columns[column].fields.push((idx, field));
+            }
+            None => {
+                let member = match stream {
+                    Some(stream) => format_ident!("stream{stream}"),
+                    None => field.ident.clone().unwrap(),
+                };
+
+                columns.push(StorageColumn {
+                    member,
+                    fields: vec![(idx, field)],
+                    stream,
+                    layout: None,
+                    attrs: StorageFieldAttrs {
+                        read_only: attrs.read_only,
+                        visibility: None,
+                        binding: 0,
+                        bit_packed: attrs.bit_packed,
+                    },
+                    kind: if is_list(field) {
+                        StorageColumnKind::Values
+                    } else if is_gpu_only(field) {
+                        StorageColumnKind::Scratch
+                    } else {
+                        StorageColumnKind::Rows
+                    },
+                });
+
+                // note: the validity or offsets column follows the values of a field
+                let auxiliary = if is_optional(field) {
+                    Some((validity_name(field), StorageColumnKind::Validity))
+                } else if is_list(field) {
+                    Some((offsets_name(field), StorageColumnKind::Offsets))
+                } else {
+                    None
+                };
+
+                if let Some((member, kind)) = auxiliary {
+                    columns.push(StorageColumn {
+                        member,
+                        fields: vec![(idx, field)],
+                        stream: None,
+                        layout: None,
+                        attrs: StorageFieldAttrs {
+                            read_only: attrs.read_only,
+                            visibility: None,
+                            binding: 0,
+                            bit_packed: kind == StorageColumnKind::Validity,
+                        },
+                        kind,
+                    });
+                    bindings.push((attrs.clone(), field));
+                    bindings.push((
+                        StorageAttrs {
+                            binding: None,
+                            ..attrs
+                        },
+                        field,
+                    ));
+                } else {
+                    bindings.push((attrs, field));
+                }
+            }
+        }
+    }
+
+    let mut bound: Vec<(u32, &Field)> = Vec::new();
+    for (column, (attrs, field)) in columns.iter_mut().zip(bindings) {
+        let binding = match attrs.binding {
+            Some(binding) => binding,
+            None => bound.last().map_or(0, |(previous, _)| previous + 1),
+        };
+
+        if let Some((_, other)) = bound.iter().find(|(b, _)| *b == binding) {
+            return Err(Error::new_spanned(
+                field,
+                format!(
+                    "storage binding {binding} is already used by `{}`",
+                    other.ident.as_ref().unwrap(),
+                ),
+            ));
+        }
+        bound.push((binding, field));
+
+        column.attrs.visibility = attrs.visibility;
+        column.attrs.binding = binding;
+
+        column.layout = match column.stream {
+            Some(_) if column.attrs.bit_packed => {
+                return Err(Error::new_spanned(
+                    field,
+                    "bit-packed fields can not be part of a stream",
+                ));
+            }
+            Some(_) => Some(stream_layout(&column.fields)?),
+            None if matches!(
+                column.kind,
+                StorageColumnKind::Validity | StorageColumnKind::Offsets
+            ) =>
+            {
+                None
+            }
+            None if column.attrs.bit_packed => {
+                if !is_bool(&field.ty) {
+                    return Err(Error::new_spanned(
+                        &field.ty,
+                        "`bit_packed` is only supported on `bool` fields",
+                    ));
+                }
+                None
+            }
+            None => padded_layout(column.fields[0].1),
+        };
+    }
+
+    Ok(columns)
+}
+
+pub fn stream_layout(fields: &[(usize, &Field)]) -> Result<Std430Struct> {
+    let fields = fields
+        .iter()
+        .map(|(_, field)| {
+            let layout = std430_type(&gpu_type(field)).ok_or_else(|| {
+                Error::new_spanned(
+                    &field.ty,
+                    "no std430 layout for this type, streams support 32-bit scalars, bool, arrays and glam vectors and matrices",
+                )
+            })?;
+
+            Ok((
+                field.ident.as_ref().unwrap().to_string(),
+                wgsl_type(&gpu_type(field)),
+                layout,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(std430_struct(fields))
+}
+
+// a single member std430 row, e.g. `[f32; 3]` padded to 16 bytes
+fn padded_layout(field: &Field) -> Option<Std430Struct> {
+    let ty = element_gpu_type(field);
+    let layout = padded_column(&ty)?;
+
+    Some(std430_struct(vec![(
+        field.ident.as_ref().unwrap().to_string(),
+        wgsl_type(&ty),
+        layout,
+    )]))
+}
+
+// `#[stream(n)]`
+pub fn parse_stream(field: &Field) -> Result<Option<u32>> {
+    let mut stream = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("stream"))
+    {
+        stream = Some(attr.parse_args::<LitInt>()?.base10_parse::<u32>()?);
+    }
+
+    Ok(stream)
+}
+
+#[derive(Clone, Default)]
+struct StorageAttrs {
+    read_only: Option<bool>,
+    visibility: Option<TokenStream>,
+    binding: Option<u32>,
+    bit_packed: bool,
+}
+
+impl StorageAttrs {
+    fn is_set(&self) -> bool {
+        self.read_only.is_some()
+            || self.visibility.is_some()
+            || self.binding.is_some()
+            || self.bit_packed
+    }
+}
+
+fn is_bool(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(type_path) if type_path.path.is_ident("bool"))
+}
+
+fn parse_storage_attrs(field: &Field) -> Result<StorageAttrs> {
+    let mut attrs = StorageAttrs::default();
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("storage"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("read_only") {
+                attrs.read_only = Some(true);
+            } else if meta.path.is_ident("read_write") {
+                attrs.read_only = Some(false);
+            } else if meta.path.is_ident("visibility") {
+                attrs.visibility = Some(shader_stages(&meta.value()?.parse::<Expr>()?)?);
+            } else if meta.path.is_ident("bit_packed") {
+                attrs.bit_packed = true;
+            } else if meta.path.is_ident("binding") {
+                attrs.binding = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u32>()?);
+            } else {
+                return Err(meta.error(
+                    "expected `read_only`, `read_write`, `bit_packed`, `visibility = ..` or `binding = ..`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+// e.g. `compute` or `vertex | fragment`
+fn shader_stages(expr: &Expr) -> Result<TokenStream> {
+    match expr {
+        Expr::Binary(binary) if matches!(binary.op, BinOp::BitOr(_)) => {
+            let left = shader_stages(&binary.left)?;
+            let right = shader_stages(&binary.right)?;
+            Ok(quote! { #left | #right })
+        }
+        Expr::Path(path) => {
+            let stage = match path
+                .path
+                .get_ident()
+                .map(|ident| ident.to_string())
+                .as_deref()
+            {
+                Some("vertex") => quote! { VERTEX },
+                Some("fragment") => quote! { FRAGMENT },
+                Some("compute") => quote! { COMPUTE },
+                Some("vertex_fragment") => quote! { VERTEX_FRAGMENT },
+                _ => {
+                    return Err(Error::new_spanned(
+                        path,
+                        "expected `vertex`, `fragment`, `compute` or `vertex_fragment`",
+                    ));
+                }
+            };
+            Ok(quote! { bevy::render::render_resource::ShaderStages::#stage })
+        }
+        _ => Err(Error::new_spanned(
+            expr,
+            "expected shader stages, e.g. `compute` or `vertex | fragment`",
+        )),
+    }
+}
+
 pub fn generate_bind_group_method(
     struct_name: &Ident,
-    fields_named: &FieldsNamed,
+    columns: &[StorageColumn],
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
     let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
     let bind_group_name = format!("storage_{struct_name_snake}_bind_group");
 
-    let bind_group_entries = fields_named.named.iter().enumerate().map(|(idx, field)| {
-        let name = field.ident.as_ref().unwrap();
-        quote! {
-            bevy::render::render_resource::BindGroupEntry {
-                binding: #idx as u32,
-                resource: bevy::render::render_resource::BindingResource::Buffer(
-                    bevy::render::render_resource::BufferBinding {
-                        buffer: &self.#name,
-                        offset: 0,
-                        size: bevy::render::render_resource::BufferSize::new(self.#name.size()),
-                    }
-                ),
-            },
-        }
+    let columns = columns.iter().map(|column| {
+        let member = &column.member;
+        quote! { self.#member.as_slice() }
     });
 
+    let column_pages = match flattened.is_empty() {
+        true => quote! {
+            vec![
+                #(#columns),*
+            ]
+        },
+        false => {
+            let flattened = flattened.iter().map(|flattened| &flattened.column);
+            quote! {
+                let mut pages = vec![
+                    #(#columns),*
+                ];
+                #(pages.extend(self.#flattened.column_pages());)*
+                pages
+            }
+        }
+    };
+
     quote! {
-        fn bind_group(
+        fn column_pages(&self) -> Vec<&[bevy_interleave::interface::paging::PlanarColumnPage]> {
+            #column_pages
+        }
+
+        fn bind_groups(
             &self,
             render_device: &bevy::render::renderer::RenderDevice,
-            layout: &bevy::render::render_resource::BindGroupLayout,
-        ) -> bevy::render::render_resource::BindGroup {
-            render_device.create_bind_group(
+            layouts: &[bevy::render::render_resource::BindGroupLayout],
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+        ) -> Vec<bevy::render::render_resource::BindGroup> {
+            bevy_interleave::interface::paging::paged_bind_groups(
+                render_device,
                 #bind_group_name,
-                &layout,
-                &[
-                    #(#bind_group_entries)*
-                ]
+                layouts,
+                partition,
+                &self.column_pages(),
+                &self.page_metadata,
             )
         }
     }
@@ -171,41 +842,415 @@ pub fn generate_bind_group_method(
 
 pub fn generate_bind_group_layout_method(
     struct_name: &Ident,
-    fields_named: &FieldsNamed,
+    columns: &[StorageColumn],
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
     let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
     let bind_group_layout_name = format!("storage_{struct_name_snake}_bind_group_layout");
 
-    let bind_group_layout_entries = fields_named.named
-        .iter()
-        .enumerate()
-        .map(|(idx, _)| {
-            quote! {
+    let bind_group_layout_entries = columns.iter().map(|column| {
+        let attrs = &column.attrs;
+        let binding = attrs.binding;
+
+        let read_only = match attrs.read_only {
+            Some(read_only) => quote! { #read_only },
+            None => quote! { read_only },
+        };
+
+        // note: writable storage is compute only by default, fragment writes need `DownlevelFlags::FRAGMENT_WRITABLE_STORAGE`
+        let visibility = match &attrs.visibility {
+            Some(visibility) => visibility.clone(),
+            None => quote! {
+                if read_only {
+                    bevy::render::render_resource::ShaderStages::VERTEX_FRAGMENT
+                        | bevy::render::render_resource::ShaderStages::COMPUTE
+                } else {
+                    bevy::render::render_resource::ShaderStages::COMPUTE
+                }
+            },
+        };
+
+        let min_binding_size = match (&column.stream, &column.layout) {
+            (Some(_), Some(layout)) => {
+                let stride = layout.size;
+                quote! { #stride }
+            }
+            // `u32` words of bit-packed bools and validity bitmasks
+            _ if column.attrs.bit_packed => quote! { 4 },
+            (_, Some(layout)) if column.kind == StorageColumnKind::Values => {
+                let stride = layout.size;
+                quote! { #stride }
+            }
+            _ if column.kind == StorageColumnKind::Values => {
+                let element = element_gpu_type(column.fields[0].1);
+                quote! { std::mem::size_of::<#element>() }
+            }
+            _ => {
+                let idx = column.fields[0].0;
+                quote! { Self::PackedType::min_binding_sizes()[#idx] }
+            }
+        };
+
+        quote! {
+            {
+                let read_only = #read_only;
+
                 bevy::render::render_resource::BindGroupLayoutEntry {
-                    binding: #idx as u32,
-                    visibility: bevy::render::render_resource::ShaderStages::VERTEX_FRAGMENT
-                        | bevy::render::render_resource::ShaderStages::COMPUTE,
+                    binding: #binding,
+                    visibility: #visibility,
                     ty: bevy::render::render_resource::BindingType::Buffer {
                         ty: bevy::render::render_resource::BufferBindingType::Storage { read_only },
                         has_dynamic_offset: false,
-                        min_binding_size: bevy::render::render_resource::BufferSize::new(Self::PackedType::min_binding_sizes()[#idx] as u64),
+                        min_binding_size: bevy::render::render_resource::BufferSize::new(#min_binding_size as u64),
                     },
                     count: None,
+                }
+            },
+        }
+    });
+
+    let binding_names = columns.iter().map(|column| column.name());
+
+    let (entries, names) = match flattened.is_empty() {
+        true => (
+            quote! {
+                vec![
+                    #(#bind_group_layout_entries)*
+                ]
+            },
+            quote! {
+                &[
+                    #(#binding_names),*
+                ]
+            },
+        ),
+        false => {
+            let nested = flattened
+                .iter()
+                .map(|flattened| {
+                    let ty = flattened.ty;
+                    quote! { <<#ty as bevy_interleave::interface::PlanarStorage>::GpuStorageType as GpuPlanarStorage> }
+                })
+                .collect::<Vec<_>>();
+            let prefixes = flattened
+                .iter()
+                .map(|flattened| flattened.column.to_string());
+
+            // note: structs with flattened fields are never generic, the names are built once
+            (
+                quote! {
+                    let mut entries = vec![
+                        #(#bind_group_layout_entries)*
+                    ];
+                    #(bevy_interleave::interface::flatten::append_layout_entries(
+                        &mut entries,
+                        #nested::bind_group_layout_entries(read_only),
+                    );)*
+                    entries
                 },
-            }
-        });
+                quote! {
+                    static BINDING_NAMES: std::sync::OnceLock<Vec<&'static str>> = std::sync::OnceLock::new();
+
+                    BINDING_NAMES.get_or_init(|| {
+                        let mut names = vec![
+                            #(#binding_names),*
+                        ];
+                        #(names.extend(bevy_interleave::interface::flatten::dotted_names(
+                            #prefixes,
+                            #nested::binding_names(),
+                        ));)*
+                        names
+                    })
+                },
+            )
+        }
+    };
 
     quote! {
-        fn bind_group_layout(
+        fn bind_group_layout_entries(
+            read_only: bool,
+        ) -> Vec<bevy::render::render_resource::BindGroupLayoutEntry> {
+            #entries
+        }
+
+        fn binding_names() -> &'static [&'static str] {
+            #names
+        }
+
+        fn bind_group_layouts(
             render_device: &bevy::render::renderer::RenderDevice,
             read_only: bool,
-        ) -> bevy::render::render_resource::BindGroupLayout {
-            render_device.create_bind_group_layout(
-                Some(#bind_group_layout_name),
-                &[
-                    #(#bind_group_layout_entries)*
-                ],
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+            paging: &bevy_interleave::interface::paging::PlanarPaging,
+        ) -> Vec<bevy::render::render_resource::BindGroupLayout> {
+            bevy_interleave::interface::paging::paged_bind_group_layouts(
+                render_device,
+                #bind_group_layout_name,
+                Self::bind_group_layout_entries(read_only),
+                partition,
+                paging,
             )
         }
     }
 }
+
+pub fn generate_wgsl_source_method(
+    struct_name: &Ident,
+    generics: &Generics,
+    fields_named: &FieldsNamed,
+    columns: &[StorageColumn],
+    flattened: &[FlattenedField],
+) -> Result<quote::__private::TokenStream> {
+    let fields = fields_named
+        .named
+        .iter()
+        .map(wgsl_field_type)
+        .collect::<Vec<WgslType>>();
+
+    let wgsl_type_params = wgsl_type_params(generics);
+
+    let wgsl_columns = columns.iter().map(|column| {
+        let name = column.name();
+        let binding = binding_name(struct_name, &name);
+        let page_prefix = format!("{}_page", raw_binding_name(struct_name, &name));
+        let loader = loader_name(struct_name, &name);
+        let storage = match column.stream {
+            None => match column.kind {
+                StorageColumnKind::Rows | StorageColumnKind::Scratch => {
+                    fields[column.fields[0].0].storage.clone()
+                }
+                StorageColumnKind::Validity | StorageColumnKind::Offsets => "u32".to_string(),
+                StorageColumnKind::Values => {
+                    wgsl_type(&element_gpu_type(column.fields[0].1)).storage
+                }
+            },
+            Some(stream) => stream_struct_name(struct_name, stream),
+        };
+        let read_only = match column.attrs.read_only {
+            Some(read_only) => quote! { Some(#read_only) },
+            None => quote! { None },
+        };
+        let bit_packed = column.attrs.bit_packed;
+        let shareable = column.kind == StorageColumnKind::Rows && !bit_packed;
+
+        quote! {
+            bevy_interleave::interface::paging::PlanarWgslColumn {
+                binding: #binding.to_string(),
+                page_prefix: #page_prefix.to_string(),
+                loader: #loader.to_string(),
+                storage: bevy_interleave::interface::substitute_wgsl_types(#storage, #wgsl_type_params),
+                read_only: #read_only,
+                bit_packed: #bit_packed,
+                shareable: #shareable,
+            }
+        }
+    });
+    let enum_consts = enum_consts(gpu_fields(fields_named).map(|(_, field)| field));
+    let stream_structs = columns
+        .iter()
+        .filter_map(|column| {
+            let stream = column.stream?;
+            let layout = column.layout.as_ref()?;
+            Some(format!(
+                "\n{}",
+                std430_wgsl_struct(
+                    &stream_struct_name(struct_name, stream),
+                    layout.wgsl_members(),
+                )
+            ))
+        })
+        .collect::<String>();
+
+    // note: each stream row is loaded once, its members are read from the loaded struct
+    let mut lets = Vec::new();
+    let mut members = vec![String::new(); fields.len()];
+    let mut field_accessors = String::new();
+    for column in columns {
+        let loader = loader_name(struct_name, &column.name());
+
+        match (column.stream, &column.layout) {
+            (Some(_), Some(layout)) => {
+                let row = wgsl_ident(&column.name());
+                lets.push((row.clone(), format!("{loader}(index)")));
+
+                for ((idx, field), member) in column.fields.iter().zip(&layout.members) {
+                    let member_name = wgsl_ident(&member.name);
+                    members[*idx] = member.ty.load(&format!("{row}.{member_name}"));
+
+                    if is_bool(&field.ty) {
+                        field_accessors.push_str(&bool_accessor(
+                            struct_name,
+                            &member.name,
+                            &member.ty.load(&format!("{loader}(index).{member_name}")),
+                        ));
+                    }
+                }
+            }
+            _ if column.kind == StorageColumnKind::Values => {
+                let (_, field) = column.fields[0];
+                let element = wgsl_type(&element_gpu_type(field));
+                field_accessors.push_str(&field_accessor(
+                    struct_name,
+                    &column.name(),
+                    &element.member,
+                    &element.load(&format!("{loader}(index)")),
+                ));
+            }
+            _ if column.kind == StorageColumnKind::Offsets => {
+                let (idx, field) = column.fields[0];
+                let name = field.ident.as_ref().unwrap().to_string();
+                members[idx] = format!("{}(index)", list_range_accessor_name(struct_name, &name));
+                field_accessors.push_str(&list_range_accessor(struct_name, &name, &loader));
+            }
+            _ if column.kind == StorageColumnKind::Validity => {
+                let name = column.fields[0].1.ident.as_ref().unwrap().to_string();
+                field_accessors.push_str(&validity_accessor(
+                    struct_name,
+                    &name,
+                    &format!("{loader}(index) != 0u"),
+                ));
+            }
+            _ => {
+                let (idx, field) = column.fields[0];
+                members[idx] = fields[idx].load(&format!("{loader}(index)"));
+
+                if is_bool(&field.ty) {
+                    field_accessors.push_str(&bool_accessor(
+                        struct_name,
+                        &column.name(),
+                        &members[idx],
+                    ));
+                } else if is_optional(field) {
+                    field_accessors.push_str(&field_accessor(
+                        struct_name,
+                        &column.name(),
+                        &fields[idx].member,
+                        &members[idx],
+                    ));
+                }
+            }
+        }
+    }
+
+    let metadata_struct = wgsl_ident(&format!("{struct_name}Pages"));
+    let metadata_binding = binding_name(struct_name, "paging");
+    let page_fn = page_fn_name(struct_name);
+
+    // note: `cpu_only` fields are not part of the packed wgsl struct
+    let members = gpu_fields(fields_named)
+        .map(|(idx, _)| members[idx].clone())
+        .collect::<Vec<_>>();
+
+    let accessor = format!(
+        "{}{field_accessors}",
+        accessor(struct_name, &lets, &members)
+    );
+    let import_path = wgsl_import_path(struct_name, generics);
+    let type_features = wgsl_type_features(generics);
+
+    let (wgsl_columns, wgsl_column_types) = match flattened.is_empty() {
+        true => (
+            quote! {
+                vec![
+                    #(#wgsl_columns),*
+                ]
+            },
+            quote! {
+                bevy_interleave::interface::substitute_wgsl_types(#stream_structs, #wgsl_type_params)
+            },
+        ),
+        false => {
+            let nested = flattened
+                .iter()
+                .map(|flattened| {
+                    let ty = flattened.ty;
+                    quote! { <<#ty as bevy_interleave::interface::PlanarStorage>::GpuStorageType as GpuPlanarStorage> }
+                })
+                .collect::<Vec<_>>();
+            let renames = flattened
+                .iter()
+                .map(|flattened| {
+                    let from = type_name_snake(flattened.ty)?;
+                    let to = raw_binding_name(struct_name, &flattened.column.to_string());
+                    Ok(quote! { #from, #to })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // note: nested columns are declared under this struct's names, e.g. `my_struct_material_value`
+            (
+                quote! {
+                    [
+                        vec![
+                            #(#wgsl_columns),*
+                        ],
+                        #(#nested::wgsl_columns()
+                            .into_iter()
+                            .map(|column| column.renamed(#renames))
+                            .collect(),)*
+                    ]
+                    .concat()
+                },
+                quote! {
+                    bevy_interleave::interface::flatten::wgsl_column_types(&[
+                        bevy_interleave::interface::substitute_wgsl_types(#stream_structs, #wgsl_type_params),
+                        #(#nested::wgsl_column_types(),)*
+                    ])
+                },
+            )
+        }
+    };
+
+    Ok(quote! {
+        fn wgsl_columns() -> Vec<bevy_interleave::interface::paging::PlanarWgslColumn> {
+            #wgsl_columns
+        }
+
+        fn wgsl_column_types() -> String {
+            #wgsl_column_types
+        }
+
+        fn wgsl_partitioned_source(
+            partition: &bevy_interleave::interface::partition::PlanarPartition,
+            paging: &bevy_interleave::interface::paging::PlanarPaging,
+            read_only: bool,
+        ) -> String {
+            let source = bevy_interleave::interface::paging::wgsl_paged_source(
+                &format!("{}{}{}", #enum_consts, Self::PackedType::wgsl_struct(), Self::wgsl_column_types()),
+                &Self::wgsl_columns(),
+                &bevy_interleave::interface::paging::PlanarWgslPages {
+                    metadata_struct: #metadata_struct,
+                    metadata_binding: #metadata_binding,
+                    page_fn: #page_fn,
+                },
+                #accessor,
+                partition,
+                paging,
+                read_only,
+            );
+
+            bevy_interleave::interface::substitute_wgsl_types(&source, #wgsl_type_params)
+        }
+
+        fn wgsl_import_path() -> String {
+            #import_path
+        }
+
+        fn wgsl_features() -> bevy::render::settings::WgpuFeatures {
+            #type_features
+        }
+    })
+}
+
+// e.g. `my_material` for `materials::MyMaterial`, the prefix of its wgsl names
+fn type_name_snake(ty: &syn::Type) -> Result<String> {
+    match ty {
+        syn::Type::Path(type_path) if type_path.qself.is_none() => {
+            let segment = type_path.path.segments.last().unwrap();
+            Ok(segment.ident.to_string().to_case(Case::Snake))
+        }
+        _ => Err(Error::new_spanned(
+            ty,
+            "flattened fields must name their struct, e.g. `material: MyMaterial`",
+        )),
+    }
+}
@@ -1,70 +1,152 @@
 use convert_case::{Case, Casing};
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::{
-    Attribute, Data, DeriveInput, Error, Fields, FieldsNamed, Ident, Path, Result,
+    DeriveInput, Error, Field, FieldsNamed, Ident, Path, Result, Type,
     parse::{Parse, ParseStream},
 };
 
+use crate::{
+    field::{
+        FlattenedField, flattened_fields, gpu_type, planar_fields, reject_storage_only,
+        without_cpu_only,
+    },
+    generics::{phantom_type, with_planar_bounds},
+    wgsl::array_length,
+};
+
 pub fn texture_bindings(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
     let name = &input.ident;
+    let generics = &with_planar_bounds(&input.generics);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let phantom_type = phantom_type(name, generics);
 
     let planar_name = Ident::new(&format!("Planar{name}"), name.span());
     let gpu_planar_name = Ident::new(&format!("PlanarTexture{name}"), name.span());
-    let planar_handle_name = Ident::new(&format!("Planar{name}Handle"), name.span());
 
-    let fields_struct = if let Data::Struct(ref data_struct) = input.data {
-        match data_struct.fields {
-            Fields::Named(ref fields) => fields,
-            _ => return Err(Error::new_spanned(input, "Unsupported struct type")),
-        }
-    } else {
+    let fields_struct = &planar_fields(input)?;
+    reject_storage_only(fields_struct, "TextureBindings")?;
+    let fields_struct = &without_cpu_only(fields_struct);
+    let flattened = &flattened_fields(input)?;
+
+    if let Some(flattened) = flattened.iter().find(|flattened| {
+        flattened
+            .field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("texture_format"))
+    }) {
         return Err(Error::new_spanned(
-            input,
-            "Planar macro only supports structs",
+            flattened.field,
+            "texture formats of a flattened field go on the fields of its struct",
         ));
-    };
+    }
+
+    // the gpu struct of each `#[planar(flatten)]` field, bound after this struct's textures
+    let flattened_columns = flattened.iter().map(|flattened| &flattened.column);
+    let flattened_types = flattened.iter().map(|flattened| {
+        let ty = flattened.ty;
+        quote! { <#ty as bevy_interleave::interface::PlanarTexture>::GpuTextureType }
+    });
+    let flattened_textures = flattened.iter().map(|flattened| {
+        let FlattenedField { column, ty, .. } = flattened;
+        quote! {
+            let #column = <<#ty as bevy_interleave::interface::PlanarTexture>::GpuTextureType as GpuPlanarTexture>::prepare_textures(
+                &source.#column,
+                render_device,
+                render_queue,
+            );
+        }
+    });
+    let flattened_names = flattened.iter().map(|flattened| &flattened.column);
 
     let field_names = fields_struct
         .named
         .iter()
         .map(|f| f.ident.as_ref().unwrap());
     let field_types = fields_struct.named.iter().map(|_| {
-        quote! { bevy::asset::Handle<bevy::image::Image> }
+        quote! { bevy::render::render_resource::Texture }
     });
 
-    let bind_group = generate_bind_group_method(name, fields_struct);
-    let bind_group_layout = generate_bind_group_layout_method(name, fields_struct);
-    let prepare = generate_prepare_method(fields_struct);
-    let get_asset_handles = generate_get_asset_handles_method(fields_struct);
+    let formats = fields_struct
+        .named
+        .iter()
+        .map(texture_format)
+        .collect::<Result<Vec<_>>>()?;
+
+    let bind_group = generate_bind_group_method(name, fields_struct, &formats, flattened);
+    let bind_group_layout =
+        generate_bind_group_layout_method(name, fields_struct, &formats, flattened);
 
-    let handle_clones = field_names.clone().map(|name| {
-        quote! { #name: source.#name.clone() }
+    let textures = fields_struct
+        .named
+        .iter()
+        .zip(&formats)
+        .map(|(field, format)| {
+            let name = field.ident.as_ref().unwrap();
+            let field_type = &field.ty;
+            let texture_name_string = format!("{name}_texture");
+
+            quote! {
+                let #name = bevy_interleave::interface::texture::create_planar_texture(
+                    render_device,
+                    render_queue,
+                    #texture_name_string,
+                    #format,
+                    bytemuck::cast_slice(source.#name.as_slice()),
+                    std::mem::size_of::<#field_type>(),
+                );
+            }
+        });
+
+    let layer_checks = fields_struct
+        .named
+        .iter()
+        .zip(&formats)
+        .map(|(field, format)| {
+            let field_type = &field.ty;
+            quote! {
+                bevy_interleave::interface::texture::check_planar_texture_layers(
+                    std::mem::size_of::<#field_type>(),
+                    #format,
+                    limits,
+                )?;
+            }
+        });
+    let flattened_checks = flattened.iter().map(|flattened| {
+        let ty = flattened.ty;
+        quote! {
+            <<#ty as bevy_interleave::interface::PlanarTexture>::GpuTextureType as GpuPlanarTexture>::check_limits(limits)?;
+        }
+    });
+
+    let texture_names = field_names.clone().map(|name| {
+        quote! { #name }
     });
 
     let expanded = quote! {
         #[derive(Debug, Clone)]
-        pub struct #gpu_planar_name {
+        pub struct #gpu_planar_name #generics #where_clause {
             #(pub #field_names: #field_types,)*
+            #(pub #flattened_columns: #flattened_types,)*
+            pub count: usize,
+            pub phantom: #phantom_type,
         }
 
-        impl bevy::render::render_asset::RenderAsset for #gpu_planar_name {
-            type SourceAsset = #planar_name;
-            type Param = ();
+        impl #impl_generics bevy::render::render_asset::RenderAsset for #gpu_planar_name #ty_generics #where_clause {
+            type SourceAsset = #planar_name #ty_generics;
+            type Param = (
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderDevice>,
+                bevy::ecs::system::lifetimeless::SRes<bevy::render::renderer::RenderQueue>,
+            );
 
             fn prepare_asset(
                 source: Self::SourceAsset,
                 _: AssetId<Self::SourceAsset>,
-                _: &mut bevy::ecs::system::SystemParamItem<Self::Param>,
+                (render_device, render_queue): &mut bevy::ecs::system::SystemParamItem<Self::Param>,
                 _: Option<&Self>,
             ) -> Result<Self, bevy::render::render_asset::PrepareAssetError<Self::SourceAsset>> {
-                let count = source.len();
-
-                Ok(Self {
-                    count,
-
-                    #(#handle_clones),*
-                })
+                Ok(Self::prepare_textures(&source, render_device, render_queue))
             }
 
             fn asset_usage(_: &Self::SourceAsset) -> bevy::asset::RenderAssetUsages {
@@ -72,26 +154,50 @@ pub fn texture_bindings(input: &DeriveInput) -> Result<quote::__private::TokenSt
             }
         }
 
-        impl GpuPlanarTexture for #gpu_planar_name {
-            type PackedType = #name;
+        impl #impl_generics GpuPlanar for #gpu_planar_name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
+            type PlanarType = #planar_name #ty_generics;
 
             fn len(&self) -> usize {
                 self.count
             }
+        }
+
+        impl #impl_generics GpuPlanarTexture for #gpu_planar_name #ty_generics #where_clause {
+            fn prepare_textures(
+                source: &Self::PlanarType,
+                render_device: &bevy::render::renderer::RenderDevice,
+                render_queue: &bevy::render::renderer::RenderQueue,
+            ) -> Self {
+                let count = source.len();
+
+                // TODO: write dirty texel ranges into the previous textures when the extent is unchanged
+                #(#textures)*
+                #(#flattened_textures)*
+
+                Self {
+                    count,
+                    phantom: std::marker::PhantomData,
+
+                    #(#texture_names,)*
+                    #(#flattened_names),*
+                }
+            }
+
+            fn check_limits(
+                limits: &bevy::render::settings::WgpuLimits,
+            ) -> Result<(), bevy_interleave::interface::partition::PlanarLimitError> {
+                #(#layer_checks)*
+                #(#flattened_checks)*
+                Ok(())
+            }
 
             #bind_group
             #bind_group_layout
-            #get_asset_handles
         }
 
-        impl PlanarTexture for #name {
-            type PackedType = #name;
-            type PlanarType = #planar_name;
-            type PlanarTypeHandle = #planar_handle_name;
-            type GpuPlanarType = #gpu_planar_name;
-
-            #get_asset_handles
-            #prepare
+        impl #impl_generics PlanarTexture for #name #ty_generics #where_clause {
+            type GpuTextureType = #gpu_planar_name #ty_generics;
         }
     };
 
@@ -101,35 +207,68 @@ pub fn texture_bindings(input: &DeriveInput) -> Result<quote::__private::TokenSt
 pub fn generate_bind_group_method(
     struct_name: &Ident,
     fields_named: &FieldsNamed,
+    formats: &[TokenStream],
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
     let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
     let bind_group_name = format!("texture_{struct_name_snake}_bind_group");
 
-    let bind_group_entries = fields_named.named.iter().enumerate().map(|(idx, field)| {
+    let views = fields_named.named.iter().zip(formats).map(|(field, format)| {
         let name = field.ident.as_ref().unwrap();
+        let field_type = &field.ty;
+
         quote! {
-            bevy::render::render_resource::BindGroupEntry {
-                binding: #idx as u32,
-                resource: bevy::render::render_resource::BindingResource::TextureView(
-                    &gpu_images.get(&self.#name).unwrap().texture_view
-                ),
-            },
+            self.#name.create_view(&bevy::render::render_resource::TextureViewDescriptor {
+                dimension: Some(bevy_interleave::interface::texture::planar_texture_view_dimension(
+                    std::mem::size_of::<#field_type>(),
+                    #format,
+                )),
+                ..Default::default()
+            })
         }
     });
 
+    let texture_views = match flattened.is_empty() {
+        true => quote! {
+            vec![
+                #(#views),*
+            ]
+        },
+        false => {
+            let flattened = flattened.iter().map(|flattened| &flattened.column);
+            quote! {
+                let mut views = vec![
+                    #(#views),*
+                ];
+                #(views.extend(self.#flattened.texture_views());)*
+                views
+            }
+        }
+    };
+
     quote! {
+        fn texture_views(&self) -> Vec<bevy::render::render_resource::TextureView> {
+            #texture_views
+        }
+
         fn bind_group(
             &self,
             render_device: &bevy::render::renderer::RenderDevice,
-            gpu_images: &bevy::render::render_asset::RenderAssets<bevy::render::texture::GpuImage>,
             layout: &bevy::render::render_resource::BindGroupLayout,
         ) -> bevy::render::render_resource::BindGroup {
+            let views = self.texture_views();
+
             render_device.create_bind_group(
                 #bind_group_name,
                 &layout,
-                &[
-                    #(#bind_group_entries)*
-                ]
+                &views
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, view)| bevy::render::render_resource::BindGroupEntry {
+                        binding: idx as u32,
+                        resource: bevy::render::render_resource::BindingResource::TextureView(view),
+                    })
+                    .collect::<Vec<_>>(),
             )
         }
     }
@@ -138,156 +277,157 @@ pub fn generate_bind_group_method(
 pub fn generate_bind_group_layout_method(
     struct_name: &Ident,
     fields_named: &FieldsNamed,
+    formats: &[TokenStream],
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
     let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
     let bind_group_layout_name = format!("texture_{struct_name_snake}_bind_group_layout");
 
-    let bind_group_layout_entries = fields_named.named.iter().enumerate().map(|(idx, field)| {
-        let name = field.ident.as_ref().unwrap();
-        let format = extract_texture_format(&field.attrs);
-
+    let bind_group_layout_entries = fields_named.named.iter().zip(formats).enumerate().map(|(idx, (field, format))| {
         let field_type = &field.ty;
 
         quote! {
-            let sample_type = #format.sample_type(None, None).unwrap();
-
-            let size = std::mem::size_of::<#field_type>();
-            let format_bpp = #format.pixel_size();
-            let depth = (size as f32 / format_bpp as f32).ceil() as u32;
-
-            let view_dimension = if depth == 1 {
-                bevy::render::render_resource::TextureViewDimension::D2  // TODO: support 3D texture sampling
-            } else {
-                bevy::render::render_resource::TextureViewDimension::D2Array
-            };
-
-            let #name = bevy::render::render_resource::BindGroupLayoutEntry {
+            bevy::render::render_resource::BindGroupLayoutEntry {
                 binding: #idx as u32,
                 visibility: bevy::render::render_resource::ShaderStages::VERTEX_FRAGMENT
                     | bevy::render::render_resource::ShaderStages::COMPUTE,
                 ty: bevy::render::render_resource::BindingType::Texture {
-                    view_dimension,
-                    sample_type,
+                    // TODO: support 3D texture sampling
+                    view_dimension: bevy_interleave::interface::texture::planar_texture_view_dimension(
+                        std::mem::size_of::<#field_type>(),
+                        #format,
+                    ),
+                    sample_type: #format.sample_type(None, None).unwrap(),
                     multisampled: false,
                 },
                 count: None,
-            };
+            },
         }
     });
 
-    let layout_names = fields_named.named.iter().map(|field| {
-        let name = field.ident.as_ref().unwrap();
-        quote! { #name }
-    });
+    let entries = match flattened.is_empty() {
+        true => quote! {
+            vec![
+                #(#bind_group_layout_entries)*
+            ]
+        },
+        false => {
+            let nested = flattened.iter().map(|flattened| flattened.ty);
+            quote! {
+                let mut entries = vec![
+                    #(#bind_group_layout_entries)*
+                ];
+                #(bevy_interleave::interface::flatten::append_layout_entries(
+                    &mut entries,
+                    <<#nested as bevy_interleave::interface::PlanarTexture>::GpuTextureType as GpuPlanarTexture>::bind_group_layout_entries(),
+                );)*
+                entries
+            }
+        }
+    };
 
     quote! {
+        fn bind_group_layout_entries() -> Vec<bevy::render::render_resource::BindGroupLayoutEntry> {
+            #entries
+        }
+
         fn bind_group_layout(
             render_device: &bevy::render::renderer::RenderDevice,
         ) -> bevy::render::render_resource::BindGroupLayout {
-            #(#bind_group_layout_entries)*
-
             render_device.create_bind_group_layout(
                 Some(#bind_group_layout_name),
-                &[
-                    #(#layout_names),*
-                ],
+                &Self::bind_group_layout_entries(),
             )
         }
     }
 }
 
-pub fn generate_prepare_method(fields_named: &FieldsNamed) -> quote::__private::TokenStream {
-    let buffers = fields_named.named.iter().map(|field| {
-        let name = field.ident.as_ref().unwrap();
-        let format = extract_texture_format(&field.attrs);
-
-        let field_type = &field.ty;
-
-        quote! {
-            let square = (self.#name.len() as f32).sqrt().ceil() as u32;
-
-            let size = std::mem::size_of::<#field_type>();
-            let format_bpp = #format.pixel_size();
-            let depth = (size as f32 / format_bpp as f32).ceil() as u32;
-
-            let mut data = bytemuck::cast_slice(self.#name.as_slice()).to_vec();
-
-            let padded_size = (square * square * depth * format_bpp as u32) as usize;
-            data.resize(padded_size, 0);
+struct TextureFormatAttr(Path);
 
-            let mut #name = bevy::image::Image::new(
-                bevy::render::render_resource::Extent3d {
-                    width: square,
-                    height: square,
-                    depth_or_array_layers: depth,
-                },
-                bevy::render::render_resource::TextureDimension::D2,
-                data,
-                #format,
-                bevy::render::render_asset::RenderAssetUsages::default(),  // TODO: if there are no CPU image derived features, set to render only
-            );
-            let #name = images.add(#name);
-        }
-    });
+impl Parse for TextureFormatAttr {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let format: Path = input.parse()?;
+        Ok(TextureFormatAttr(format))
+    }
+}
 
-    let buffer_names = fields_named.named.iter().map(|field| {
-        let name = field.ident.as_ref().unwrap();
-        quote! { #name }
-    });
+// `#[texture_format(..)]`, or a default for scalars, scalar arrays and glam types
+fn texture_format(field: &Field) -> Result<TokenStream> {
+    if let Some(attr) = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("texture_format"))
+    {
+        let TextureFormatAttr(format) = attr.parse_args::<TextureFormatAttr>()?;
+        return Ok(quote! { #format });
+    }
 
-    quote! {
-        fn prepare(
-            &self,
-            images: &mut bevy::asset::Assets<bevy::image::Image>,
-        ) -> Self {
-            #(#buffers)*
+    let format = default_texture_format(&gpu_type(field)).ok_or_else(|| {
+        Error::new_spanned(
+            &field.ty,
+            "no default texture format for this type, add `#[texture_format(TextureFormat::..)]` to the field",
+        )
+    })?;
+    let format = format_ident!("{format}");
 
-            Self {
-                #(#buffer_names),*
-            }
-        }
-    }
+    Ok(quote! { bevy::render::render_resource::TextureFormat::#format })
 }
 
-pub fn generate_get_asset_handles_method(
-    fields_named: &FieldsNamed,
-) -> quote::__private::TokenStream {
-    let buffer_names = fields_named.named.iter().map(|field| {
-        let name = field.ident.as_ref().unwrap();
-        quote! { self.#name.clone() }
-    });
+// note: elements wider than a texel continue on the next layer, e.g. `Mat4` spans four `Rgba32Float` layers
+fn default_texture_format(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Array(array) => {
+            let scalar = scalar_kind(&array.elem)?;
+
+            let format = match (array_length(&array.len)?, scalar) {
+                (2, "Float") => "Rg32Float",
+                (2, "Sint") => "Rg32Sint",
+                (2, "Uint") => "Rg32Uint",
+                (3 | 4, "Float") => "Rgba32Float",
+                (3 | 4, "Sint") => "Rgba32Sint",
+                (3 | 4, "Uint") => "Rgba32Uint",
+                (_, "Float") => "R32Float",
+                (_, "Sint") => "R32Sint",
+                _ => "R32Uint",
+            };
 
-    quote! {
-        fn get_asset_handles(&self) -> Vec<bevy::asset::Handle<bevy::image::Image>> {
-            vec![
-                #(#buffer_names),*
-            ]
+            Some(format)
         }
-    }
-}
-
-struct TextureFormatAttr(Path);
+        Type::Path(type_path) => {
+            let segment = type_path.path.segments.last()?;
+
+            let format = match segment.ident.to_string().as_str() {
+                "f32" => "R32Float",
+                "i32" => "R32Sint",
+                "u32" => "R32Uint",
+                "bool" => "R8Unorm",
+                "Vec2" => "Rg32Float",
+                "Vec3" | "Vec3A" | "Vec4" | "Quat" | "Mat2" | "Mat3A" | "Mat4" => "Rgba32Float",
+                // note: 12 byte columns would straddle `Rgba32Float` texels
+                "Mat3" => "R32Float",
+                "IVec2" => "Rg32Sint",
+                "IVec3" | "IVec4" => "Rgba32Sint",
+                "UVec2" => "Rg32Uint",
+                "UVec3" | "UVec4" => "Rgba32Uint",
+                _ => return None,
+            };
 
-impl Parse for TextureFormatAttr {
-    fn parse(input: ParseStream) -> Result<Self> {
-        let format: Path = input.parse()?;
-        Ok(TextureFormatAttr(format))
+            Some(format)
+        }
+        Type::Paren(paren) => default_texture_format(&paren.elem),
+        Type::Group(group) => default_texture_format(&group.elem),
+        _ => None,
     }
 }
 
-fn extract_texture_format(attributes: &[Attribute]) -> TokenStream {
-    for attr in attributes {
-        if attr.path().is_ident("texture_format") {
-            if let Ok(parsed) = attr.parse_args::<TextureFormatAttr>() {
-                let TextureFormatAttr(format) = parsed;
-                return quote! { #format };
-            } else {
-                panic!("error parsing texture_format attribute");
-            }
-        }
+fn scalar_kind(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Path(type_path) => match type_path.path.get_ident()?.to_string().as_str() {
+            "f32" => Some("Float"),
+            "i32" => Some("Sint"),
+            "u32" => Some("Uint"),
+            _ => None,
+        },
+        _ => None,
     }
-
-    panic!(
-        "no texture_format attribute found, add `#[texture_format(Ident)]` to the field declarations"
-    );
 }
@@ -0,0 +1,85 @@
+use convert_case::{Case, Casing};
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Result};
+
+use crate::wgsl::wgsl_ident;
+
+pub fn generate_planar_enum(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
+    let name = &input.ident;
+
+    let Data::Enum(ref data_enum) = input.data else {
+        return Err(Error::new_spanned(
+            input,
+            "PlanarEnum macro only supports enums",
+        ));
+    };
+
+    if !is_repr_u32(input)? {
+        return Err(Error::new_spanned(
+            input,
+            "PlanarEnum requires `#[repr(u32)]`",
+        ));
+    }
+
+    if let Some(variant) = data_enum
+        .variants
+        .iter()
+        .find(|variant| !matches!(variant.fields, Fields::Unit))
+    {
+        return Err(Error::new_spanned(
+            variant,
+            "PlanarEnum only supports fieldless variants",
+        ));
+    }
+
+    let prefix = name.to_string().to_case(Case::UpperSnake);
+    let consts = data_enum.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let const_name = wgsl_ident(&format!(
+            "{prefix}_{}",
+            ident.to_string().to_case(Case::UpperSnake)
+        ));
+        let line = format!("const {const_name}: u32 = {{}}u;\n");
+
+        quote! { format!(#line, #name::#ident as u32) }
+    });
+
+    let expanded = quote! {
+        impl bevy_interleave::interface::PlanarEnum for #name {
+            fn discriminant(self) -> u32 {
+                self as u32
+            }
+
+            fn wgsl_consts() -> String {
+                [
+                    #(#consts),*
+                ]
+                .concat()
+            }
+        }
+
+        // safety: a fieldless `#[repr(u32)]` enum is a `u32` without padding
+        unsafe impl bytemuck::NoUninit for #name {}
+    };
+
+    Ok(expanded)
+}
+
+fn is_repr_u32(input: &DeriveInput) -> Result<bool> {
+    let mut repr_u32 = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("u32") {
+                repr_u32 = true;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(repr_u32)
+}
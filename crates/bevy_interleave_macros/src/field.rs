@@ -0,0 +1,308 @@
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Field, Fields, FieldsNamed, GenericArgument, Ident, Index, LitStr,
+    Member, PathArguments, Result, Type, parse_quote,
+};
+
+use crate::wgsl::{WgslType, wgsl_type};
+
+// `#[planar(enum)]`, `#[planar(cpu_only)]`, `#[planar(gpu_only)]`, `#[planar(skip)]`, `#[planar(rename = "..")]` or `#[planar(flatten)]`
+#[derive(Default)]
+pub struct PlanarFieldAttrs {
+    // a fieldless `#[derive(PlanarEnum)]` enum uploaded as its `u32` discriminant, see `PlanarEnum`
+    pub enumeration: bool,
+    // never uploaded, e.g. a `String` label
+    pub cpu_only: bool,
+    // a zeroed GPU column of `len()` rows without CPU data, e.g. sort keys written by compute
+    pub gpu_only: bool,
+    // no column, read back as its default
+    pub skip: bool,
+    // the column, `ordered_field_names` and wgsl name
+    pub rename: Option<Ident>,
+    // a nested planar struct stored as its `Planar*` struct, its columns follow the struct's own columns
+    pub flatten: bool,
+}
+
+pub fn planar_field_attrs(field: &Field) -> Result<PlanarFieldAttrs> {
+    let mut attrs = PlanarFieldAttrs::default();
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("planar"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("enum") {
+                attrs.enumeration = true;
+            } else if meta.path.is_ident("cpu_only") {
+                attrs.cpu_only = true;
+            } else if meta.path.is_ident("gpu_only") {
+                attrs.gpu_only = true;
+            } else if meta.path.is_ident("skip") {
+                attrs.skip = true;
+            } else if meta.path.is_ident("rename") {
+                attrs.rename = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+            } else if meta.path.is_ident("flatten") {
+                attrs.flatten = true;
+            } else {
+                return Err(meta.error(
+                    "expected `enum`, `cpu_only`, `gpu_only`, `skip`, `rename = \"..\"` or `flatten`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+// note: malformed attributes are reported by the `Planar` derive
+pub fn is_enum(field: &Field) -> bool {
+    planar_field_attrs(field).is_ok_and(|attrs| attrs.enumeration)
+}
+
+pub fn is_cpu_only(field: &Field) -> bool {
+    planar_field_attrs(field).is_ok_and(|attrs| attrs.cpu_only)
+}
+
+pub fn is_gpu_only(field: &Field) -> bool {
+    planar_field_attrs(field).is_ok_and(|attrs| attrs.gpu_only)
+}
+
+pub fn is_skipped(field: &Field) -> bool {
+    planar_field_attrs(field).is_ok_and(|attrs| attrs.skip)
+}
+
+pub fn is_flattened(field: &Field) -> bool {
+    planar_field_attrs(field).is_ok_and(|attrs| attrs.flatten)
+}
+
+pub fn struct_fields(input: &DeriveInput) -> Result<&Fields> {
+    let Data::Struct(ref data_struct) = input.data else {
+        return Err(Error::new_spanned(
+            input,
+            "Planar macro only supports structs",
+        ));
+    };
+
+    match data_struct.fields {
+        Fields::Named(_) | Fields::Unnamed(_) => Ok(&data_struct.fields),
+        Fields::Unit => Err(Error::new_spanned(input, "Unsupported struct type")),
+    }
+}
+
+// the columns of a struct named by `column_name`, skipping `#[planar(skip)]` and `#[planar(flatten)]` fields
+pub fn planar_fields(input: &DeriveInput) -> Result<FieldsNamed> {
+    let named = struct_fields(input)?
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| !is_skipped(field) && !is_flattened(field))
+        .map(|(idx, field)| {
+            let mut column = field.clone();
+            column.ident = Some(column_name(field, idx));
+            column.colon_token.get_or_insert_with(Default::default);
+            column
+        })
+        .collect();
+
+    Ok(FieldsNamed {
+        brace_token: Default::default(),
+        named,
+    })
+}
+
+// a `#[planar(flatten)]` field, e.g. `material: Material` stored as `material: PlanarMaterial`
+pub struct FlattenedField<'a> {
+    // member of the `Planar*` and gpu structs, prefix of the nested column names
+    pub column: Ident,
+    // e.g. `value.material` of the packed struct
+    pub member: Member,
+    pub ty: &'a Type,
+    pub field: &'a Field,
+}
+
+// note: nested columns follow the struct's own columns, in field order
+pub fn flattened_fields(input: &DeriveInput) -> Result<Vec<FlattenedField<'_>>> {
+    Ok(struct_fields(input)?
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| is_flattened(field))
+        .map(|(idx, field)| FlattenedField {
+            column: column_name(field, idx),
+            member: packed_member(field, idx),
+            ty: &field.ty,
+            field,
+        })
+        .collect())
+}
+
+// e.g. `position`, `_0` for the first field of a tuple struct or `#[planar(rename = "..")]`
+pub fn column_name(field: &Field, idx: usize) -> Ident {
+    match planar_field_attrs(field)
+        .ok()
+        .and_then(|attrs| attrs.rename)
+    {
+        Some(rename) => rename,
+        None => field
+            .ident
+            .clone()
+            .unwrap_or_else(|| format_ident!("_{idx}")),
+    }
+}
+
+// e.g. `value.position` or `value.0` of the packed struct
+pub fn packed_member(field: &Field, idx: usize) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(idx)),
+    }
+}
+
+// the type of the row-aligned column of a field, e.g. `u32` for enums, `T` for `Option<T>` or the `u32` end offsets of `Vec<T>`
+pub fn gpu_type(field: &Field) -> Type {
+    if is_list(field) {
+        parse_quote!(u32)
+    } else {
+        element_gpu_type(field)
+    }
+}
+
+// the type values are uploaded as, e.g. `T` for the flat values of `Vec<T>`
+pub fn element_gpu_type(field: &Field) -> Type {
+    if is_enum(field) {
+        parse_quote!(u32)
+    } else {
+        value_type(field).clone()
+    }
+}
+
+// packed wgsl struct member, `vec2<u32>` value range of `Vec<T>` fields
+pub fn wgsl_field_type(field: &Field) -> WgslType {
+    if is_list(field) {
+        WgslType {
+            storage: "u32".to_string(),
+            member: "vec2<u32>".to_string(),
+        }
+    } else {
+        wgsl_type(&gpu_type(field))
+    }
+}
+
+// `T` of an `Option<T>` field, stored as a dense `Vec<T>` and a `PlanarValidity` bitmask
+pub fn option_inner(ty: &Type) -> Option<&Type> {
+    generic_inner(ty, "Option")
+}
+
+// `T` of a `Vec<T>` field, stored as a flat `Vec<T>` and `u32` end offsets, see `bevy_interleave::interface::list`
+pub fn list_inner(ty: &Type) -> Option<&Type> {
+    generic_inner(ty, "Vec")
+}
+
+fn generic_inner<'a>(ty: &'a Type, ident: &str) -> Option<&'a Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != ident {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn is_optional(field: &Field) -> bool {
+    option_inner(&field.ty).is_some()
+}
+
+pub fn is_list(field: &Field) -> bool {
+    list_inner(&field.ty).is_some()
+}
+
+// element type of the planar `Vec` column
+pub fn value_type(field: &Field) -> &Type {
+    option_inner(&field.ty)
+        .or_else(|| list_inner(&field.ty))
+        .unwrap_or(&field.ty)
+}
+
+// note: the validity bitmasks of `Option<T>`, the offsets of `Vec<T>` and `gpu_only` fields are only uploaded by `StorageBindings`
+pub fn reject_storage_only(fields_named: &FieldsNamed, derive: &str) -> Result<()> {
+    match gpu_fields(fields_named)
+        .find(|(_, field)| is_optional(field) || is_list(field) || is_gpu_only(field))
+    {
+        Some((_, field)) => Err(Error::new_spanned(
+            field,
+            format!(
+                "`Option`, `Vec` and `#[planar(gpu_only)]` fields are not supported by `{derive}`, use `StorageBindings`"
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+// fields with a GPU column and their index within the packed struct, skipping `cpu_only` fields
+pub fn gpu_fields(fields_named: &FieldsNamed) -> impl Iterator<Item = (usize, &Field)> {
+    fields_named
+        .named
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| !is_cpu_only(field))
+}
+
+// e.g. for texture bindings numbered by position
+pub fn without_cpu_only(fields_named: &FieldsNamed) -> FieldsNamed {
+    let mut fields = fields_named.clone();
+    fields.named = gpu_fields(fields_named)
+        .map(|(_, field)| field.clone())
+        .collect();
+    fields
+}
+
+// fields of the CPU `Planar*` struct, skipping `gpu_only` fields
+pub fn cpu_fields(fields_named: &FieldsNamed) -> impl Iterator<Item = &Field> {
+    fields_named
+        .named
+        .iter()
+        .filter(|field| !is_gpu_only(field))
+}
+
+// e.g. `normal_validity`
+pub fn validity_name(field: &Field) -> Ident {
+    format_ident!("{}_validity", field.ident.as_ref().unwrap())
+}
+
+// e.g. `neighbors_offsets`
+pub fn offsets_name(field: &Field) -> Ident {
+    format_ident!("{}_offsets", field.ident.as_ref().unwrap())
+}
+
+// wgsl `const`s of the distinct enum types among `fields`, evaluated when the shader source is built
+pub fn enum_consts<'a>(fields: impl Iterator<Item = &'a Field>) -> quote::__private::TokenStream {
+    let mut types: Vec<&Type> = Vec::new();
+    for field in fields.filter(|field| is_enum(field)) {
+        let ty = value_type(field);
+        if !types
+            .iter()
+            .any(|other| quote!(#other).to_string() == quote!(#ty).to_string())
+        {
+            types.push(ty);
+        }
+    }
+
+    if types.is_empty() {
+        return quote! { String::new() };
+    }
+
+    quote! {
+        [
+            #(<#types as bevy_interleave::interface::PlanarEnum>::wgsl_consts()),*
+        ]
+        .concat()
+    }
+}
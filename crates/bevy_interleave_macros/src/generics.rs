@@ -0,0 +1,112 @@
+use convert_case::{Case, Casing};
+use quote::quote;
+use syn::{Generics, Ident, Token, Type, TypeParamBound, parse_quote, punctuated::Punctuated};
+
+type TypeParamBounds = Punctuated<TypeParamBound, Token![+]>;
+
+// gpu structs only hold buffers or textures, the packed type keeps their type parameters used
+pub fn phantom_type(struct_name: &Ident, generics: &Generics) -> quote::__private::TokenStream {
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    quote! { std::marker::PhantomData<fn() -> #struct_name #ty_generics> }
+}
+
+// note: `Planar*` structs are reflected assets, their type parameters need the same bounds as a reflected field
+pub fn with_planar_bounds(generics: &Generics) -> Generics {
+    with_bounds(
+        generics,
+        parse_quote!(bevy::reflect::Reflectable + bevy::reflect::FromReflect),
+    )
+}
+
+// type parameters of the storage backends are substituted in wgsl through `PlanarWgslType`
+pub fn with_wgsl_bounds(generics: &Generics) -> Generics {
+    with_bounds(
+        generics,
+        parse_quote!(bevy_interleave::interface::PlanarWgslType),
+    )
+}
+
+fn with_bounds(generics: &Generics, bounds: TypeParamBounds) -> Generics {
+    let mut generics = generics.clone();
+    let params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bounds));
+    }
+
+    generics
+}
+
+// e.g. a field of type `T`, its layout is only known once instantiated
+pub fn is_type_param(ty: &Type, generics: &Generics) -> bool {
+    let Type::Path(type_path) = ty else {
+        return false;
+    };
+
+    type_path.qself.is_none()
+        && type_path
+            .path
+            .get_ident()
+            .is_some_and(|ident| generics.type_params().any(|param| param.ident == *ident))
+}
+
+// e.g. `bevy_interleave::my_scalar_f32::storage`, type parameters keep the module unique per instantiation
+pub fn wgsl_import_path(struct_name: &Ident, generics: &Generics) -> quote::__private::TokenStream {
+    let struct_name_snake = struct_name.to_string().to_case(Case::Snake);
+
+    let segments = generics.type_params().map(|param| {
+        let ident = &param.ident;
+        quote! { <#ident as bevy_interleave::interface::PlanarWgslType>::wgsl_path_segment() }
+    });
+
+    if generics.type_params().next().is_none() {
+        let import_path = format!("bevy_interleave::{struct_name_snake}::storage");
+        return quote! { #import_path.to_string() };
+    }
+
+    quote! {
+        format!(
+            "bevy_interleave::{}_{}::storage",
+            #struct_name_snake,
+            [
+                #(#segments),*
+            ]
+            .join("_"),
+        )
+    }
+}
+
+// union of the device features of the type parameters, e.g. `SHADER_F16` once instantiated with `PlanarF16`
+pub fn wgsl_type_features(generics: &Generics) -> quote::__private::TokenStream {
+    let type_params = generics.type_params().map(|param| {
+        let ident = &param.ident;
+
+        quote! { | <#ident as bevy_interleave::interface::PlanarWgslType>::wgsl_features() }
+    });
+
+    quote! {
+        bevy::render::settings::WgpuFeatures::empty() #(#type_params)*
+    }
+}
+
+// e.g. `("T", "f32")`, type parameters are replaced in the shader source once instantiated
+// note: naga_oil can not compose modules declaring an `alias`
+pub fn wgsl_type_params(generics: &Generics) -> quote::__private::TokenStream {
+    let type_params = generics.type_params().map(|param| {
+        let ident = &param.ident;
+        let name = ident.to_string();
+
+        quote! { (#name, <#ident as bevy_interleave::interface::PlanarWgslType>::wgsl_type()) }
+    });
+
+    quote! {
+        &[
+            #(#type_params),*
+        ]
+    }
+}
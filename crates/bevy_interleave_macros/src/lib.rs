@@ -6,7 +6,7 @@ use syn::{DeriveInput, parse_macro_input};
 mod planar;
 use planar::generate_planar_struct;
 
-#[proc_macro_derive(Planar)]
+#[proc_macro_derive(Planar, attributes(planar))]
 pub fn planar_macro_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -33,10 +33,30 @@ pub fn reflect_interleaved_macro_derive(input: TokenStream) -> TokenStream {
     TokenStream::from(output)
 }
 
+mod enumeration;
+use enumeration::generate_planar_enum;
+
+#[proc_macro_derive(PlanarEnum)]
+pub fn planar_enum_macro_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let output = match generate_planar_enum(&input) {
+        Ok(quote) => quote,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    TokenStream::from(output)
+}
+
+mod field;
+mod generics;
+mod std430;
+mod wgsl;
+
 mod bindings;
 use bindings::storage::storage_bindings;
 
-#[proc_macro_derive(StorageBindings)]
+#[proc_macro_derive(StorageBindings, attributes(storage, stream))]
 pub fn storage_bindings_macro_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -48,6 +68,21 @@ pub fn storage_bindings_macro_derive(input: TokenStream) -> TokenStream {
     TokenStream::from(output)
 }
 
+use bindings::packed::packed_storage_bindings;
+
+// note: implements `PlanarStorage` for the generated `<Name>Packed` marker, both storage derives may coexist
+#[proc_macro_derive(PackedStorageBindings)]
+pub fn packed_storage_bindings_macro_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let output = match packed_storage_bindings(&input) {
+        Ok(quote) => quote,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    TokenStream::from(output)
+}
+
 use bindings::texture::texture_bindings;
 
 #[proc_macro_derive(TextureBindings, attributes(texture_format))]
@@ -1,30 +1,34 @@
 use quote::quote;
-use syn::{Data, DeriveInput, Error, Fields, FieldsNamed, Result};
+use syn::{DeriveInput, FieldsNamed, Ident, Result};
+
+use crate::{
+    bindings::storage::{parse_stream, stream_layout},
+    field::{
+        FlattenedField, flattened_fields, gpu_fields, gpu_type, is_cpu_only, planar_fields,
+        wgsl_field_type,
+    },
+    std430::padded_column,
+    wgsl::packed_struct,
+};
 
 pub fn generate_reflect_interleaved(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
     let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let fields_struct = if let Data::Struct(ref data_struct) = input.data {
-        match data_struct.fields {
-            Fields::Named(ref fields) => fields,
-            _ => return Err(Error::new_spanned(input, "Unsupported struct type")),
-        }
-    } else {
-        return Err(Error::new_spanned(
-            input,
-            "Planar macro only supports structs",
-        ));
-    };
+    let fields_struct = &planar_fields(input)?;
+    let flattened = &flattened_fields(input)?;
 
-    let min_binding_size_method = generate_min_binding_size_method(fields_struct);
-    let ordered_field_names_method = generate_ordered_field_names_method(fields_struct);
+    let min_binding_size_method = generate_min_binding_size_method(fields_struct, flattened);
+    let ordered_field_names_method = generate_ordered_field_names_method(fields_struct, flattened);
+    let wgsl_struct_method = generate_wgsl_struct_method(name, fields_struct);
 
     let expanded = quote! {
-        impl ReflectInterleaved for #name {
-            type PackedType = #name;
+        impl #impl_generics ReflectInterleaved for #name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
 
             #min_binding_size_method
             #ordered_field_names_method
+            #wgsl_struct_method
         }
     };
 
@@ -33,23 +37,85 @@ pub fn generate_reflect_interleaved(input: &DeriveInput) -> Result<quote::__priv
 
 pub fn generate_min_binding_size_method(
     fields_named: &FieldsNamed,
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
-    let min_binding_sizes = fields_named.named.iter().map(|f| {
-        let field_type = &f.ty;
-        quote! {
-            std::mem::size_of::<#field_type>()
-        }
-    });
+    let stream_strides = stream_strides(fields_named);
+
+    // note: the wgsl array stride of padded columns, e.g. 16 for `[f32; 3]` and 4 for `bool`, 0 for `cpu_only` fields
+    // fields of a `#[stream(n)]` report the std430 stride of their stream, i.e. the size of the shared binding
+    let min_binding_sizes =
+        fields_named
+            .named
+            .iter()
+            .zip(stream_strides)
+            .map(|(f, stream_stride)| {
+                if is_cpu_only(f) {
+                    return quote! { 0 };
+                }
+
+                if let Some(stride) = stream_stride {
+                    return quote! { #stride };
+                }
+
+                let field_type = gpu_type(f);
+                match padded_column(&field_type) {
+                    Some(layout) => {
+                        let stride = layout.stride();
+                        quote! { #stride }
+                    }
+                    None => quote! {
+                        std::mem::size_of::<#field_type>()
+                    },
+                }
+            });
+
+    if flattened.is_empty() {
+        return quote! {
+            fn min_binding_sizes() -> &'static [usize] {
+                &[#(#min_binding_sizes),*]
+            }
+        };
+    }
+
+    let nested = flattened.iter().map(|flattened| flattened.ty);
 
     quote! {
         fn min_binding_sizes() -> &'static [usize] {
-            &[#(#min_binding_sizes),*]
+            static MIN_BINDING_SIZES: std::sync::OnceLock<Vec<usize>> = std::sync::OnceLock::new();
+
+            MIN_BINDING_SIZES.get_or_init(|| {
+                let mut sizes = vec![#(#min_binding_sizes),*];
+                #(sizes.extend_from_slice(<#nested as bevy_interleave::interface::ReflectInterleaved>::min_binding_sizes());)*
+                sizes
+            })
         }
     }
 }
 
+// std430 row size of the stream of each field, invalid streams are reported by the `StorageBindings` derive
+fn stream_strides(fields_named: &FieldsNamed) -> Vec<Option<usize>> {
+    let streams = fields_named
+        .named
+        .iter()
+        .map(|field| parse_stream(field).ok().flatten())
+        .collect::<Vec<_>>();
+
+    streams
+        .iter()
+        .map(|stream| {
+            let stream = (*stream)?;
+            let fields = gpu_fields(fields_named)
+                .filter(|(idx, _)| streams[*idx] == Some(stream))
+                .collect::<Vec<_>>();
+
+            stream_layout(&fields).ok().map(|layout| layout.size)
+        })
+        .collect()
+}
+
 pub fn generate_ordered_field_names_method(
     fields_named: &FieldsNamed,
+    flattened: &[FlattenedField],
 ) -> quote::__private::TokenStream {
     let string_field_names = fields_named.named.iter().map(|field| {
         let name = field.ident.as_ref().unwrap();
@@ -57,11 +123,62 @@ pub fn generate_ordered_field_names_method(
         quote! { #name_str }
     });
 
+    if flattened.is_empty() {
+        return quote! {
+            fn ordered_field_names() -> &'static [&'static str] {
+                &[
+                    #(#string_field_names),*
+                ]
+            }
+        };
+    }
+
+    let nested = flattened.iter().map(|flattened| {
+        let prefix = flattened.column.to_string();
+        let ty = flattened.ty;
+
+        quote! {
+            names.extend(bevy_interleave::interface::flatten::dotted_names(
+                #prefix,
+                <#ty as bevy_interleave::interface::ReflectInterleaved>::ordered_field_names(),
+            ));
+        }
+    });
+
+    // note: structs with flattened fields are never generic, the names are built once
     quote! {
         fn ordered_field_names() -> &'static [&'static str] {
-            &[
-                #(#string_field_names),*
-            ]
+            static ORDERED_FIELD_NAMES: std::sync::OnceLock<Vec<&'static str>> = std::sync::OnceLock::new();
+
+            ORDERED_FIELD_NAMES.get_or_init(|| {
+                let mut names = vec![
+                    #(#string_field_names),*
+                ];
+                #(#nested)*
+                names
+            })
+        }
+    }
+}
+
+pub fn generate_wgsl_struct_method(
+    struct_name: &Ident,
+    fields_named: &FieldsNamed,
+) -> quote::__private::TokenStream {
+    let fields = gpu_fields(fields_named)
+        .map(|(_, field)| {
+            (
+                field.ident.as_ref().unwrap().to_string(),
+                wgsl_field_type(field),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let wgsl_struct = packed_struct(struct_name, &fields);
+
+    quote! {
+        fn wgsl_struct() -> &'static str {
+            #wgsl_struct
         }
     }
 }
@@ -1,36 +1,145 @@
 use quote::quote;
-use syn::{Data, DeriveInput, Error, Fields, FieldsNamed, Ident, Result};
+use syn::{DeriveInput, Error, Field, FieldsNamed, Ident, Member, Result};
+
+use crate::{
+    field::{
+        FlattenedField, cpu_fields, flattened_fields, is_flattened, is_gpu_only, is_list,
+        is_optional, is_skipped, offsets_name, packed_member, planar_field_attrs, planar_fields,
+        struct_fields, validity_name, value_type,
+    },
+    generics::with_planar_bounds,
+};
 
 pub fn generate_planar_struct(input: &DeriveInput) -> Result<quote::__private::TokenStream> {
     let name = &input.ident;
+    let generics = &with_planar_bounds(&input.generics);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let planar_name = Ident::new(&format!("Planar{name}"), name.span());
     let planar_handle_name = Ident::new(&format!("Planar{name}Handle"), name.span());
 
-    let fields_struct = if let Data::Struct(ref data_struct) = input.data {
-        match data_struct.fields {
-            Fields::Named(ref fields) => fields,
-            _ => return Err(Error::new_spanned(input, "Unsupported struct type")),
+    let struct_fields = struct_fields(input)?;
+
+    for field in struct_fields {
+        let attrs = planar_field_attrs(field)?;
+
+        if attrs.flatten && (attrs.enumeration || attrs.cpu_only || attrs.gpu_only || attrs.skip) {
+            return Err(Error::new_spanned(
+                field,
+                "`flatten` can only be combined with `rename`",
+            ));
         }
-    } else {
-        return Err(Error::new_spanned(
-            input,
-            "Planar macro only supports structs",
-        ));
-    };
 
-    let field_names = fields_struct
+        // note: the dotted column names of a nested struct are built once per type
+        if attrs.flatten && !input.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                field,
+                "`flatten` is not supported on generic structs",
+            ));
+        }
+
+        if attrs.skip
+            && (attrs.enumeration || attrs.cpu_only || attrs.gpu_only || attrs.rename.is_some())
+        {
+            return Err(Error::new_spanned(
+                field,
+                "`skip` can not be combined with other `planar` attributes",
+            ));
+        }
+
+        if attrs.cpu_only && attrs.gpu_only {
+            return Err(Error::new_spanned(
+                field,
+                "a field can not be both `cpu_only` and `gpu_only`",
+            ));
+        }
+
+        if attrs.gpu_only && (is_optional(field) || is_list(field)) {
+            return Err(Error::new_spanned(
+                field,
+                "`gpu_only` fields can not be `Option` or `Vec`",
+            ));
+        }
+    }
+
+    let fields_struct = &planar_fields(input)?;
+    let flattened = flattened_fields(input)?;
+
+    let columns = fields_struct
         .named
         .iter()
-        .map(|f| f.ident.as_ref().unwrap());
-    let field_types = fields_struct.named.iter().map(|og| {
-        let ty = &og.ty;
-        quote! { Vec<#ty> }
+        .map(|field| field.ident.as_ref().unwrap())
+        .chain(flattened.iter().map(|flattened| &flattened.column))
+        .collect::<Vec<_>>();
+    for (idx, column) in columns.iter().enumerate() {
+        if columns.iter().take(idx).any(|other| other == column) {
+            return Err(Error::new_spanned(
+                column,
+                format!("duplicate column `{column}`, see `#[planar(rename = \"..\")]`"),
+            ));
+        }
+    }
+
+    let members = PackedMembers {
+        columns: struct_fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !is_skipped(field) && !is_flattened(field))
+            .map(|(idx, field)| packed_member(field, idx))
+            .collect(),
+        flattened,
+        skipped: struct_fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| is_skipped(field))
+            .map(|(idx, field)| packed_member(field, idx))
+            .collect(),
+    };
+
+    let flattened_planar_fields = members.flattened.iter().map(|flattened| {
+        let FlattenedField { column, ty, .. } = flattened;
+        quote! { pub #column: <#ty as bevy_interleave::interface::PlanarSync>::PlanarType, }
+    });
+
+    let planar_fields = cpu_fields(fields_struct).map(|field| {
+        let name = field.ident.as_ref().unwrap();
+        let ty = value_type(field);
+
+        if is_optional(field) {
+            let validity = validity_name(field);
+            quote! {
+                pub #name: Vec<#ty>,
+                pub #validity: bevy_interleave::interface::validity::PlanarValidity,
+            }
+        } else if is_list(field) {
+            let offsets = offsets_name(field);
+            quote! {
+                pub #name: Vec<#ty>,
+                pub #offsets: Vec<u32>,
+            }
+        } else {
+            quote! { pub #name: Vec<#ty>, }
+        }
     });
 
-    let conversion_methods = generate_conversion_methods(name, fields_struct);
-    let get_set_methods = generate_accessor_setter_methods(name, fields_struct);
-    let len_method = generate_len_method(fields_struct);
-    let subset_method = generate_subset_method(fields_struct);
+    // note: direct writes through `pub` columns are not tracked, see `Planar::mark_all_dirty`
+    let column_accessors = cpu_fields(fields_struct).map(|field| {
+        let name = field.ident.as_ref().unwrap();
+        let name_mut = Ident::new(&format!("{name}_mut"), name.span());
+        let ty = value_type(field);
+
+        quote! {
+            pub fn #name_mut(&mut self) -> &mut Vec<#ty> {
+                bevy_interleave::interface::Planar::mark_all_dirty(self);
+                &mut self.#name
+            }
+        }
+    });
+
+    let conversion_methods = generate_conversion_methods(name, fields_struct, &members);
+    let get_set_methods = generate_accessor_setter_methods(name, fields_struct, &members);
+    let len_method = generate_len_method(fields_struct, &members);
+    let subset_method = generate_subset_method(fields_struct, &members);
+    let dirty_methods = generate_flattened_dirty_methods(fields_struct, &members);
 
     let expanded = quote! {
         #[derive(
@@ -43,40 +152,79 @@ pub fn generate_planar_struct(input: &DeriveInput) -> Result<quote::__private::T
             serde::Serialize,
             serde::Deserialize,
         )]
-        pub struct #planar_name {
-            #(pub #field_names: #field_types,)*
+        pub struct #planar_name #generics #where_clause {
+            #(#planar_fields)*
+            #(#flattened_planar_fields)*
+
+            #[reflect(ignore)]
+            #[serde(skip)]
+            pub dirty_ranges: bevy_interleave::interface::dirty::PlanarDirtyRanges,
+        }
+
+        impl #impl_generics #planar_name #ty_generics #where_clause {
+            #(#column_accessors)*
         }
 
-        impl Planar for #planar_name {
-            type PackedType = #name;
+        impl #impl_generics Planar for #planar_name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
 
             #conversion_methods
             #get_set_methods
             #len_method
             #subset_method
+
+            fn dirty_ranges(&self) -> &bevy_interleave::interface::dirty::PlanarDirtyRanges {
+                &self.dirty_ranges
+            }
+
+            fn dirty_ranges_mut(&mut self) -> &mut bevy_interleave::interface::dirty::PlanarDirtyRanges {
+                &mut self.dirty_ranges
+            }
+
+            #dirty_methods
         }
 
-        #[derive(bevy::prelude::Component, Clone, Debug, Default, PartialEq, bevy::reflect::Reflect)]
+        #[derive(bevy::prelude::Component, Clone, Debug, PartialEq, bevy::reflect::Reflect)]
         #[require(bevy::prelude::Transform, bevy::prelude::Visibility)]
-        pub struct #planar_handle_name(pub bevy::asset::Handle<#planar_name>);
+        pub struct #planar_handle_name #generics (pub bevy::asset::Handle<#planar_name #ty_generics>) #where_clause;
+
+        // note: a derived `Default` would require `Default` type parameters
+        impl #impl_generics Default for #planar_handle_name #ty_generics #where_clause {
+            fn default() -> Self {
+                Self(Default::default())
+            }
+        }
 
-        impl bevy_interleave::interface::PlanarHandle<#planar_name> for #planar_handle_name {
-            fn handle(&self) -> &bevy::asset::Handle<#planar_name> {
+        impl #impl_generics bevy_interleave::interface::PlanarHandle<#planar_name #ty_generics> for #planar_handle_name #ty_generics #where_clause {
+            fn handle(&self) -> &bevy::asset::Handle<#planar_name #ty_generics> {
                 &self.0
             }
         }
 
-        impl bevy::render::sync_component::SyncComponent for #planar_handle_name {
+        impl #impl_generics bevy::render::sync_component::SyncComponent for #planar_handle_name #ty_generics #where_clause {
             type Target = Self;
         }
+
+        impl #impl_generics bevy_interleave::interface::PlanarSync for #name #ty_generics #where_clause {
+            type PackedType = #name #ty_generics;
+            type PlanarType = #planar_name #ty_generics;
+            type PlanarTypeHandle = #planar_handle_name #ty_generics;
+        }
     };
 
     Ok(expanded)
 }
 
-pub fn generate_len_method(fields_named: &FieldsNamed) -> quote::__private::TokenStream {
-    if let Some(first_field) = fields_named.named.first() {
-        let first_field_name = first_field.ident.as_ref().unwrap();
+pub fn generate_len_method(
+    fields_named: &FieldsNamed,
+    members: &PackedMembers,
+) -> quote::__private::TokenStream {
+    if let Some(first_field) = cpu_fields(fields_named).next() {
+        // note: the values of `Vec<T>` fields are not row-aligned, their offsets are
+        let first_field_name = match is_list(first_field) {
+            true => offsets_name(first_field),
+            false => first_field.ident.clone().unwrap(),
+        };
         quote! {
             fn is_empty(&self) -> bool {
                 self.#first_field_name.is_empty()
@@ -86,6 +234,17 @@ pub fn generate_len_method(fields_named: &FieldsNamed) -> quote::__private::Toke
                 self.#first_field_name.len()
             }
         }
+    } else if let Some(flattened) = members.flattened.first() {
+        let column = &flattened.column;
+        quote! {
+            fn is_empty(&self) -> bool {
+                bevy_interleave::interface::Planar::is_empty(&self.#column)
+            }
+
+            fn len(&self) -> usize {
+                bevy_interleave::interface::Planar::len(&self.#column)
+            }
+        }
     } else {
         quote! {
             fn is_empty(&self) -> bool {
@@ -99,29 +258,74 @@ pub fn generate_len_method(fields_named: &FieldsNamed) -> quote::__private::Toke
     }
 }
 
+// members of the packed struct per column, e.g. `0` for tuple structs, the `#[planar(flatten)]` and `#[planar(skip)]` members
+pub struct PackedMembers<'a> {
+    pub columns: Vec<Member>,
+    pub flattened: Vec<FlattenedField<'a>>,
+    pub skipped: Vec<Member>,
+}
+
 pub fn generate_accessor_setter_methods(
     struct_name: &Ident,
     fields_named: &FieldsNamed,
+    members: &PackedMembers,
 ) -> quote::__private::TokenStream {
-    let packed_assignments = fields_named.named.iter().map(|field| {
-        let name = field.ident.as_ref().unwrap();
-        quote! { #name: self.#name[index].clone() }
+    let packed_assignments = fields_named
+        .named
+        .iter()
+        .zip(&members.columns)
+        .map(|(field, member)| packed_field(field, member))
+        .chain(members.flattened.iter().map(flattened_packed_field));
+    let skipped = &members.skipped;
+
+    let flattened_set_assignments = members.flattened.iter().map(|flattened| {
+        let FlattenedField { column, member, .. } = flattened;
+        quote! {
+            bevy_interleave::interface::Planar::set(&mut self.#column, index, value.#member.clone());
+        }
     });
 
-    let set_assignments = fields_named.named.iter().map(|field| {
+    let set_assignments = fields_named.named.iter().zip(&members.columns).enumerate().map(|(idx, (field, member))| {
         let name = field.ident.as_ref().unwrap();
-        quote! { self.#name[index] = value.#name.clone(); }
+
+        if is_gpu_only(field) {
+            quote! {}
+        } else if is_optional(field) {
+            let validity = validity_name(field);
+            quote! {
+                self.#name[index] = value.#member.clone().unwrap_or_default();
+                self.#validity.set(index, value.#member.is_some());
+            }
+        } else if is_list(field) {
+            let offsets = offsets_name(field);
+
+            // note: a resized list moves the values of every later row
+            quote! {
+                if bevy_interleave::interface::list::set_list(&mut self.#offsets, &mut self.#name, index, &value.#member) {
+                    let len = self.len();
+                    self.dirty_ranges.mark(#idx, index..len);
+                }
+            }
+        } else {
+            quote! { self.#name[index] = value.#member.clone(); }
+        }
     });
 
+    let column_count = fields_named.named.len();
+
     quote! {
-        fn get(&self, index: usize) -> #struct_name {
+        fn get(&self, index: usize) -> Self::PackedType {
             #struct_name {
-                #(#packed_assignments),*
+                #(#packed_assignments,)*
+                #(#skipped: Default::default(),)*
             }
         }
 
-        fn set(&mut self, index: usize, value: #struct_name) {
-            #(#set_assignments);*
+        fn set(&mut self, index: usize, value: Self::PackedType) {
+            #(#set_assignments)*
+            #(#flattened_set_assignments)*
+
+            self.dirty_ranges.mark_columns(#column_count, index..index + 1);
         }
     }
 }
@@ -129,38 +333,69 @@ pub fn generate_accessor_setter_methods(
 pub fn generate_conversion_methods(
     struct_name: &Ident,
     fields_named: &FieldsNamed,
+    members: &PackedMembers,
 ) -> quote::__private::TokenStream {
+    let skipped = &members.skipped;
     let (from_interleaved_fields, to_interleaved_fields_templates): (Vec<_>, Vec<_>) = fields_named
         .named
         .iter()
-        .map(|field| {
+        .zip(&members.columns)
+        .map(|(field, member)| {
             let name = field.ident.as_ref().unwrap();
 
-            let from_interleaved_field = quote! {
-                #name: packed.iter().map(|x| x.#name.clone()).collect()
-            };
-            let to_interleaved_field_template = quote! {
-                #name: self.#name[index].clone()
+            let from_interleaved_field = if is_gpu_only(field) {
+                quote! {}
+            } else if is_optional(field) {
+                let validity = validity_name(field);
+                quote! {
+                    #name: packed.iter().map(|x| x.#member.clone().unwrap_or_default()).collect(),
+                    #validity: packed.iter().map(|x| x.#member.is_some()).collect(),
+                }
+            } else if is_list(field) {
+                let offsets = offsets_name(field);
+                quote! {
+                    #name: packed.iter().flat_map(|x| x.#member.iter().cloned()).collect(),
+                    #offsets: bevy_interleave::interface::list::list_ends(packed.iter().map(|x| x.#member.len())),
+                }
+            } else {
+                quote! {
+                    #name: packed.iter().map(|x| x.#member.clone()).collect(),
+                }
             };
+            let to_interleaved_field_template = packed_field(field, member);
 
             (from_interleaved_field, to_interleaved_field_template)
         })
         .unzip();
 
+    let flattened_from_interleaved = members.flattened.iter().map(|flattened| {
+        let FlattenedField { column, member, .. } = flattened;
+        quote! {
+            #column: bevy_interleave::interface::Planar::from_interleaved(
+                packed.iter().map(|x| x.#member.clone()).collect(),
+            ),
+        }
+    });
+    let flattened_to_interleaved = members.flattened.iter().map(flattened_packed_field);
+
     let to_interleaved_method = quote! {
-        fn to_interleaved(&self) -> Vec<#struct_name> {
+        fn to_interleaved(&self) -> Vec<Self::PackedType> {
             (0..self.len())
                 .map(|index| #struct_name {
-                    #(#to_interleaved_fields_templates),*
+                    #(#to_interleaved_fields_templates,)*
+                    #(#flattened_to_interleaved,)*
+                    #(#skipped: Default::default(),)*
                 })
                 .collect()
         }
     };
 
     let conversion_methods = quote! {
-        fn from_interleaved(packed: Vec<#struct_name>) -> Self {
+        fn from_interleaved(packed: Vec<Self::PackedType>) -> Self {
             Self {
-                #(#from_interleaved_fields),*
+                #(#from_interleaved_fields)*
+                #(#flattened_from_interleaved)*
+                dirty_ranges: Default::default(),
             }
         }
         #to_interleaved_method
@@ -169,22 +404,68 @@ pub fn generate_conversion_methods(
     conversion_methods
 }
 
-pub fn generate_subset_method(fields_named: &FieldsNamed) -> proc_macro2::TokenStream {
+pub fn generate_subset_method(
+    fields_named: &FieldsNamed,
+    members: &PackedMembers,
+) -> proc_macro2::TokenStream {
     let mut new_planes_fields = Vec::new();
     let mut push_self_index = Vec::new();
     let mut planes = Vec::new();
 
-    for field in &fields_named.named {
+    for field in cpu_fields(fields_named) {
         let name = field.ident.as_ref().unwrap();
 
+        planes.push(quote! {
+            #name
+        });
+
+        if is_list(field) {
+            let offsets = offsets_name(field);
+
+            new_planes_fields.push(quote! {
+                let mut #name = Vec::new();
+                let mut #offsets = Vec::with_capacity(indices.len());
+            });
+            push_self_index.push(quote! {
+                #name.extend_from_slice(&self.#name[bevy_interleave::interface::list::list_range(&self.#offsets, index)]);
+                #offsets.push(#name.len() as u32);
+            });
+            planes.push(quote! {
+                #offsets
+            });
+            continue;
+        }
+
         new_planes_fields.push(quote! {
             let mut #name = Vec::with_capacity(indices.len());
         });
         push_self_index.push(quote! {
-            #name.push(self.#name[index]);
+            #name.push(self.#name[index].clone());
+        });
+
+        if is_optional(field) {
+            let validity = validity_name(field);
+
+            new_planes_fields.push(quote! {
+                let mut #validity = bevy_interleave::interface::validity::PlanarValidity::default();
+            });
+            push_self_index.push(quote! {
+                #validity.push(self.#validity.get(index));
+            });
+            planes.push(quote! {
+                #validity
+            });
+        }
+    }
+
+    for flattened in &members.flattened {
+        let column = &flattened.column;
+
+        new_planes_fields.push(quote! {
+            let #column = bevy_interleave::interface::Planar::subset(&self.#column, indices);
         });
         planes.push(quote! {
-            #name
+            #column
         });
     }
 
@@ -197,8 +478,94 @@ pub fn generate_subset_method(fields_named: &FieldsNamed) -> proc_macro2::TokenS
             }
 
             Self {
-                #(#planes),*
+                #(#planes,)*
+                dirty_ranges: Default::default(),
+            }
+        }
+    }
+}
+
+// columns of `#[planar(flatten)]` fields follow the struct's own columns, their dirty ranges are kept by the nested planar
+pub fn generate_flattened_dirty_methods(
+    fields_named: &FieldsNamed,
+    members: &PackedMembers,
+) -> quote::__private::TokenStream {
+    let own_columns = fields_named.named.len();
+    let columns = members
+        .flattened
+        .iter()
+        .map(|flattened| &flattened.column)
+        .collect::<Vec<_>>();
+
+    let mark_all_dirty = quote! {
+        fn mark_all_dirty(&mut self) {
+            let len = self.len();
+            self.dirty_ranges.mark_columns(#own_columns, 0..len);
+            #(bevy_interleave::interface::Planar::mark_all_dirty(&mut self.#columns);)*
+        }
+    };
+
+    let Some((last, flattened)) = members.flattened.split_last() else {
+        return mark_all_dirty;
+    };
+
+    let nested = flattened.iter().map(|flattened| {
+        let FlattenedField { column, ty, .. } = flattened;
+        quote! {
+            let columns = <#ty as bevy_interleave::interface::ReflectInterleaved>::ordered_field_names().len();
+            if column < columns {
+                bevy_interleave::interface::Planar::mark_dirty(&mut self.#column, column, rows);
+                return;
             }
+            column -= columns;
         }
+    });
+    let last = &last.column;
+    let column = match flattened.is_empty() {
+        true => quote! { column },
+        false => quote! { mut column },
+    };
+
+    quote! {
+        #mark_all_dirty
+
+        fn clear_dirty_ranges(&mut self) {
+            self.dirty_ranges.clear();
+            #(bevy_interleave::interface::Planar::clear_dirty_ranges(&mut self.#columns);)*
+        }
+
+        fn mark_dirty(&mut self, column: usize, rows: std::ops::Range<usize>) {
+            if column < #own_columns {
+                self.dirty_ranges.mark(column, rows);
+                return;
+            }
+
+            let #column = column - #own_columns;
+            #(#nested)*
+            bevy_interleave::interface::Planar::mark_dirty(&mut self.#last, column, rows);
+        }
+    }
+}
+
+fn flattened_packed_field(flattened: &FlattenedField) -> quote::__private::TokenStream {
+    let FlattenedField { column, member, .. } = flattened;
+    quote! { #member: bevy_interleave::interface::Planar::get(&self.#column, index) }
+}
+
+// `None` rows of `Option<T>` fields are unset in their validity bitmask, `Vec<T>` rows are sliced from their values
+// note: `gpu_only` fields have no CPU data, they are read back as their default
+fn packed_field(field: &Field, member: &Member) -> quote::__private::TokenStream {
+    let name = field.ident.as_ref().unwrap();
+
+    if is_gpu_only(field) {
+        quote! { #member: Default::default() }
+    } else if is_optional(field) {
+        let validity = validity_name(field);
+        quote! { #member: self.#validity.get(index).then(|| self.#name[index].clone()) }
+    } else if is_list(field) {
+        let offsets = offsets_name(field);
+        quote! { #member: self.#name[bevy_interleave::interface::list::list_range(&self.#offsets, index)].to_vec() }
+    } else {
+        quote! { #member: self.#name[index].clone() }
     }
 }
@@ -0,0 +1,293 @@
+use quote::quote;
+use syn::{Field, Type};
+
+use crate::{
+    field::element_gpu_type,
+    wgsl::{WgslType, array_length},
+};
+
+// a host `offset..offset + len` copied to `offset` within the std430 member
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub host: usize,
+    pub offset: usize,
+    pub len: usize,
+    pub widen_bool: bool,
+}
+
+impl Segment {
+    fn copy(host: usize, offset: usize, len: usize) -> Self {
+        Self {
+            host,
+            offset,
+            len,
+            widen_bool: false,
+        }
+    }
+
+    pub fn tokens(&self) -> quote::__private::TokenStream {
+        let Segment {
+            host,
+            offset,
+            len,
+            widen_bool,
+        } = *self;
+
+        quote! {
+            bevy_interleave::interface::std430::Std430Segment {
+                host: #host,
+                offset: #offset,
+                len: #len,
+                widen_bool: #widen_bool,
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Std430Type {
+    pub align: usize,
+    pub size: usize,
+    // tightly packed rust size
+    pub host_size: usize,
+    pub segments: Vec<Segment>,
+}
+
+impl Std430Type {
+    fn scalar() -> Self {
+        Self::plain(4, 4)
+    }
+
+    // identical rust and wgsl bytes, e.g. `vec4<f32>`
+    fn plain(align: usize, size: usize) -> Self {
+        Self::padded(align, size, size, size)
+    }
+
+    // the first `len` bytes of a `host_size` rust value, e.g. `Vec3A`
+    fn padded(align: usize, size: usize, host_size: usize, len: usize) -> Self {
+        Self {
+            align,
+            size,
+            host_size,
+            segments: vec![Segment::copy(0, 0, len)],
+        }
+    }
+
+    // `count` tightly packed rust elements copied into wgsl elements `stride` bytes apart
+    fn strided(
+        align: usize,
+        size: usize,
+        element: &Std430Type,
+        count: usize,
+        stride: usize,
+    ) -> Self {
+        let mut segments: Vec<Segment> = Vec::new();
+
+        for idx in 0..count {
+            for segment in &element.segments {
+                let segment = Segment {
+                    host: segment.host + idx * element.host_size,
+                    offset: segment.offset + idx * stride,
+                    ..*segment
+                };
+
+                // note: contiguous copies are merged, e.g. `array<u32, 8>` is a single segment
+                match segments.last_mut() {
+                    Some(last)
+                        if !last.widen_bool
+                            && !segment.widen_bool
+                            && last.host + last.len == segment.host
+                            && last.offset + last.len == segment.offset =>
+                    {
+                        last.len += segment.len;
+                    }
+                    _ => segments.push(segment),
+                }
+            }
+        }
+
+        Self {
+            align,
+            size,
+            host_size: element.host_size * count,
+            segments,
+        }
+    }
+
+    // element stride of a wgsl `array<T>`
+    pub fn stride(&self) -> usize {
+        round_up(self.align, self.size)
+    }
+
+    fn widens_bool(&self) -> bool {
+        self.segments.iter().any(|segment| segment.widen_bool)
+    }
+}
+
+fn round_up(align: usize, value: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+// std430 layout of a field, mirroring `wgsl_type`, `None` for types without a known layout
+pub fn std430_type(ty: &Type) -> Option<Std430Type> {
+    match ty {
+        Type::Array(array) => {
+            let len = array_length(&array.len)?;
+            let element = std430_type(&array.elem)?;
+
+            // note: wgsl has no `bool` arrays or vectors in host-shareable memory
+            if element.widens_bool() {
+                return None;
+            }
+
+            let scalar = element.size == 4 && element.host_size == 4;
+            match len {
+                2 if scalar => Some(Std430Type::plain(8, 8)),
+                3 if scalar => Some(Std430Type::padded(16, 12, 12, 12)),
+                4 if scalar => Some(Std430Type::plain(16, 16)),
+                2..=4 => None,
+                _ => {
+                    let stride = round_up(element.align, element.size);
+                    Some(Std430Type::strided(
+                        element.align,
+                        stride * len,
+                        &element,
+                        len,
+                        stride,
+                    ))
+                }
+            }
+        }
+        Type::Path(type_path) => {
+            let segment = type_path.path.segments.last()?;
+
+            match segment.ident.to_string().as_str() {
+                "f32" | "i32" | "u32" => Some(Std430Type::scalar()),
+                "bool" => Some(Std430Type {
+                    align: 4,
+                    size: 4,
+                    host_size: 1,
+                    segments: vec![Segment {
+                        host: 0,
+                        offset: 0,
+                        len: 4,
+                        widen_bool: true,
+                    }],
+                }),
+                "Vec2" | "IVec2" | "UVec2" => Some(Std430Type::plain(8, 8)),
+                "Vec3" | "IVec3" | "UVec3" => Some(Std430Type::padded(16, 12, 12, 12)),
+                "Vec3A" => Some(Std430Type::padded(16, 12, 16, 12)),
+                "Vec4" | "IVec4" | "UVec4" | "Quat" => Some(Std430Type::plain(16, 16)),
+                "Mat2" => Some(Std430Type::plain(8, 16)),
+                "Mat3" => Some(Std430Type::strided(
+                    16,
+                    48,
+                    &Std430Type::padded(16, 12, 12, 12),
+                    3,
+                    16,
+                )),
+                "Mat3A" => Some(Std430Type::padded(16, 48, 48, 48)),
+                "Mat4" => Some(Std430Type::plain(16, 64)),
+                _ => None,
+            }
+        }
+        Type::Paren(paren) => std430_type(&paren.elem),
+        Type::Group(group) => std430_type(&group.elem),
+        _ => None,
+    }
+}
+
+// layout of a planar column whose wgsl array stride differs from the rust element, e.g. `[f32; 3]` and `Mat3`
+// are padded to `vec3<f32>` strides and `bool` is promoted to `u32` on upload, `None` for columns uploaded as is
+pub fn padded_column(ty: &Type) -> Option<Std430Type> {
+    let layout = std430_type(ty)?;
+
+    let verbatim = layout.stride() == layout.host_size
+        && matches!(
+            layout.segments.as_slice(),
+            [Segment {
+                host: 0,
+                offset: 0,
+                ..
+            }]
+        );
+
+    (!verbatim).then_some(layout)
+}
+
+pub struct Std430Member {
+    pub name: String,
+    pub ty: WgslType,
+    pub layout: Std430Type,
+    pub offset: usize,
+}
+
+pub struct Std430Struct {
+    pub members: Vec<Std430Member>,
+    // array stride of the struct
+    pub size: usize,
+}
+
+impl Std430Struct {
+    // names and types of the wgsl struct
+    pub fn wgsl_members(&self) -> impl Iterator<Item = (&str, &WgslType)> {
+        self.members
+            .iter()
+            .map(|member| (member.name.as_str(), &member.ty))
+    }
+}
+
+pub fn std430_struct(fields: Vec<(String, WgslType, Std430Type)>) -> Std430Struct {
+    let mut cursor = 0;
+    let mut align = 4;
+
+    let members = fields
+        .into_iter()
+        .map(|(name, ty, layout)| {
+            let offset = round_up(layout.align, cursor);
+            cursor = offset + layout.size;
+            align = align.max(layout.align);
+
+            Std430Member {
+                name,
+                ty,
+                layout,
+                offset,
+            }
+        })
+        .collect();
+
+    Std430Struct {
+        members,
+        size: round_up(align, cursor),
+    }
+}
+
+// `Std430Column`s reading the planar `source.#field` columns
+pub fn std430_columns<'a>(
+    fields: impl Iterator<Item = (&'a Field, &'a Std430Member)>,
+) -> Vec<quote::__private::TokenStream> {
+    fields
+        .map(|(field, member)| {
+            let name = field.ident.as_ref().unwrap();
+            let field_type = element_gpu_type(field);
+            let offset = member.offset;
+            let segments = member
+                .layout
+                .segments
+                .iter()
+                .map(|segment| segment.tokens());
+
+            quote! {
+                bevy_interleave::interface::std430::Std430Column {
+                    bytes: bytemuck::cast_slice(source.#name.as_slice()),
+                    host_size: std::mem::size_of::<#field_type>(),
+                    offset: #offset,
+                    segments: &[
+                        #(#segments),*
+                    ],
+                }
+            }
+        })
+        .collect()
+}
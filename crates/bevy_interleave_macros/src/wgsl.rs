@@ -0,0 +1,295 @@
+use convert_case::{Case, Casing};
+use syn::{Error, Expr, FieldsNamed, Ident, Lit, Result, Type};
+
+use crate::field::{element_gpu_type, gpu_fields};
+
+pub struct WgslType {
+    // element type of the planar `array<T>` storage binding
+    pub storage: String,
+    // member type within the packed wgsl struct
+    pub member: String,
+}
+
+impl WgslType {
+    fn same(ty: impl Into<String>) -> Self {
+        let ty = ty.into();
+
+        Self {
+            storage: ty.clone(),
+            member: ty,
+        }
+    }
+
+    // converts a loaded storage element into the struct member
+    pub fn load(&self, expr: &str) -> String {
+        if self.storage == self.member {
+            return expr.to_string();
+        }
+
+        match self.member.as_str() {
+            "bool" => format!("{expr} != 0u"),
+            member => format!("{member}({expr})"),
+        }
+    }
+}
+
+pub fn wgsl_type(ty: &Type) -> WgslType {
+    match ty {
+        Type::Array(array) => {
+            let element = wgsl_type(&array.elem).member;
+
+            match array_length(&array.len) {
+                Some(len @ 2..=4) => WgslType::same(format!("vec{len}<{element}>")),
+                Some(len) => WgslType::same(format!("array<{element}, {len}>")),
+                None => WgslType::same(format!("array<{element}>")),
+            }
+        }
+        Type::Path(type_path) => {
+            let segment = type_path.path.segments.last().unwrap();
+            let ident = segment.ident.to_string();
+
+            if let Some(wgsl) = glam_type(&ident) {
+                return WgslType::same(wgsl);
+            }
+
+            match ident.as_str() {
+                "bool" => WgslType {
+                    storage: "u32".to_string(),
+                    member: "bool".to_string(),
+                },
+                // scalars keep their name, user defined pod types are expected to be declared in wgsl with the same name
+                _ => WgslType::same(ident),
+            }
+        }
+        Type::Paren(paren) => wgsl_type(&paren.elem),
+        Type::Group(group) => wgsl_type(&group.elem),
+        _ => WgslType::same(quote::quote!(#ty).to_string()),
+    }
+}
+
+// rust scalars without a wgsl storage type, passing them through would only fail at shader compilation
+const UNSUPPORTED_SCALARS: &[&str] = &[
+    "u8", "i8", "u16", "i16", "u64", "i64", "u128", "i128", "usize", "isize", "f16", "f64", "char",
+];
+
+pub fn reject_unsupported_scalars(fields_named: &FieldsNamed) -> Result<()> {
+    for (_, field) in gpu_fields(fields_named) {
+        if let Some(scalar) = unsupported_scalar(&element_gpu_type(field)) {
+            // note: `half::f16` is not reflectable, `PlanarF16` substitutes `f16` through `PlanarWgslType`
+            let message = match scalar.as_str() {
+                "f16" => {
+                    "`f16` columns need a type parameter instantiated with `PlanarF16`".to_string()
+                }
+                _ => format!("`{scalar}` has no wgsl storage type, use `u32`, `i32` or `f32`"),
+            };
+            return Err(Error::new_spanned(field, message));
+        }
+    }
+
+    Ok(())
+}
+
+fn unsupported_scalar(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Array(array) => unsupported_scalar(&array.elem),
+        Type::Paren(paren) => unsupported_scalar(&paren.elem),
+        Type::Group(group) => unsupported_scalar(&group.elem),
+        Type::Path(type_path) => {
+            let ident = type_path.path.segments.last()?.ident.to_string();
+            UNSUPPORTED_SCALARS
+                .contains(&ident.as_str())
+                .then_some(ident)
+        }
+        _ => None,
+    }
+}
+
+fn glam_type(ident: &str) -> Option<&'static str> {
+    let wgsl = match ident {
+        "Vec2" => "vec2<f32>",
+        "Vec3" | "Vec3A" => "vec3<f32>",
+        "Vec4" | "Quat" => "vec4<f32>",
+        "IVec2" => "vec2<i32>",
+        "IVec3" => "vec3<i32>",
+        "IVec4" => "vec4<i32>",
+        "UVec2" => "vec2<u32>",
+        "UVec3" => "vec3<u32>",
+        "UVec4" => "vec4<u32>",
+        "Mat2" => "mat2x2<f32>",
+        "Mat3" | "Mat3A" => "mat3x3<f32>",
+        "Mat4" => "mat4x4<f32>",
+        _ => return None,
+    };
+
+    Some(wgsl)
+}
+
+pub fn array_length(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Int(int) => int.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// note: naga renames identifiers ending in a digit or clashing with a keyword, which naga_oil rejects for imported modules
+pub fn wgsl_ident(name: &str) -> String {
+    let renamed = name.ends_with(|c: char| c.is_ascii_digit())
+        || naga::keywords::wgsl::RESERVED.contains(&name);
+
+    if renamed {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn accessor_name(struct_name: &Ident) -> String {
+    wgsl_ident(&format!(
+        "get_{}",
+        struct_name.to_string().to_case(Case::Snake)
+    ))
+}
+
+// e.g. `get_my_struct_bool_field`
+pub fn field_accessor_name(struct_name: &Ident, field_name: &str) -> String {
+    wgsl_ident(&format!(
+        "get_{}",
+        raw_binding_name(struct_name, field_name)
+    ))
+}
+
+// reads a single field without loading the whole row, e.g. `get_my_struct_normal(index)`
+pub fn field_accessor(struct_name: &Ident, field_name: &str, ty: &str, value: &str) -> String {
+    format!(
+        "\nfn {}(index: u32) -> {ty} {{\n    return {value};\n}}\n",
+        field_accessor_name(struct_name, field_name),
+    )
+}
+
+// reads a `bool` field, hiding its promotion or bit-packing
+pub fn bool_accessor(struct_name: &Ident, field_name: &str, value: &str) -> String {
+    field_accessor(struct_name, field_name, "bool", value)
+}
+
+// e.g. `has_my_struct_normal(index)`, whether an `Option<T>` field is `Some`
+pub fn validity_accessor(struct_name: &Ident, field_name: &str, value: &str) -> String {
+    format!(
+        "\nfn {}(index: u32) -> bool {{\n    return {value};\n}}\n",
+        wgsl_ident(&format!(
+            "has_{}",
+            raw_binding_name(struct_name, field_name)
+        )),
+    )
+}
+
+// e.g. `get_my_struct_neighbors_range`
+pub fn list_range_accessor_name(struct_name: &Ident, field_name: &str) -> String {
+    field_accessor_name(struct_name, &format!("{field_name}_range"))
+}
+
+// `vec2<u32>(start, end)` values of a `Vec<T>` field's row, read with `get_my_struct_neighbors(value)`
+pub fn list_range_accessor(struct_name: &Ident, field_name: &str, offsets_loader: &str) -> String {
+    format!(
+        "\nfn {}(index: u32) -> vec2<u32> {{\n    let end = {offsets_loader}(index);\n    if index == 0u {{\n        return vec2<u32>(0u, end);\n    }}\n    return vec2<u32>({offsets_loader}(index - 1u), end);\n}}\n",
+        list_range_accessor_name(struct_name, field_name),
+    )
+}
+
+// prefixed to avoid clashing with other planar types in the same shader
+pub fn binding_name(struct_name: &Ident, field_name: &str) -> String {
+    wgsl_ident(&raw_binding_name(struct_name, field_name))
+}
+
+pub fn raw_binding_name(struct_name: &Ident, field_name: &str) -> String {
+    format!(
+        "{}_{field_name}",
+        struct_name.to_string().to_case(Case::Snake)
+    )
+}
+
+// reads one element of a paged column
+pub fn loader_name(struct_name: &Ident, field_name: &str) -> String {
+    wgsl_ident(&format!(
+        "load_{}",
+        raw_binding_name(struct_name, field_name)
+    ))
+}
+
+pub fn page_fn_name(struct_name: &Ident) -> String {
+    wgsl_ident(&format!(
+        "{}_page",
+        struct_name.to_string().to_case(Case::Snake)
+    ))
+}
+
+pub fn packed_struct(struct_name: &Ident, fields: &[(String, WgslType)]) -> String {
+    let members = fields
+        .iter()
+        .map(|(name, ty)| format!("    {}: {},\n", wgsl_ident(name), ty.member))
+        .collect::<String>();
+
+    let struct_name = wgsl_ident(&struct_name.to_string());
+    format!("struct {struct_name} {{\n{members}}}\n")
+}
+
+// `lets` are loaded once per row, e.g. an interleaved stream, `members` construct the packed struct in field order
+pub fn accessor(struct_name: &Ident, lets: &[(String, String)], members: &[String]) -> String {
+    let accessor = accessor_name(struct_name);
+    let packed_name = wgsl_ident(&struct_name.to_string());
+
+    let lets = lets
+        .iter()
+        .map(|(name, expr)| format!("    let {name} = {expr};\n"))
+        .collect::<String>();
+
+    let members = members
+        .iter()
+        .map(|member| format!("        {member},\n"))
+        .collect::<String>();
+
+    format!(
+        "fn {accessor}(index: u32) -> {packed_name} {{\n{lets}    return {packed_name}(\n{members}    );\n}}\n"
+    )
+}
+
+// host-shareable twin of the packed struct, e.g. `bool` members are stored as `u32`
+pub fn std430_wgsl_struct<'a>(
+    name: &str,
+    members: impl IntoIterator<Item = (&'a str, &'a WgslType)>,
+) -> String {
+    let members = members
+        .into_iter()
+        .map(|(name, ty)| format!("    {}: {},\n", wgsl_ident(name), ty.storage))
+        .collect::<String>();
+
+    format!("struct {name} {{\n{members}}}\n")
+}
+
+pub fn packed_storage_name(struct_name: &Ident) -> String {
+    wgsl_ident(&format!("{struct_name}Packed"))
+}
+
+// e.g. `MyStructStream0_`
+pub fn stream_struct_name(struct_name: &Ident, stream: u32) -> String {
+    wgsl_ident(&format!("{struct_name}Stream{stream}"))
+}
+
+pub fn packed_storage_accessor<'a>(
+    struct_name: &Ident,
+    members: impl IntoIterator<Item = (&'a str, &'a WgslType)>,
+    loader: &str,
+) -> String {
+    let members = members
+        .into_iter()
+        .map(|(name, ty)| ty.load(&format!("packed.{}", wgsl_ident(name))))
+        .collect::<Vec<_>>();
+
+    accessor(
+        struct_name,
+        &[("packed".to_string(), format!("{loader}(index)"))],
+        &members,
+    )
+}
@@ -4,17 +4,27 @@ pub use bevy::render::render_resource::TextureFormat;
 pub use crate::interface::{
     GpuPlanar,
     GpuPlanarStorage,
+    GpuPlanarTexture,
     Planar,
+    PlanarEnum,
     PlanarHandle,
+    PlanarStorage,
     PlanarSync,
     PlanarTexture,
-    // texture::{
-    //     PlanarTextureBindGroup,
-    //     PlanarTextureLayouts,
-    //     PlanarTexturePlugin,
-    // },
+    PlanarF16,
+    PlanarWgslType,
     ReflectInterleaved,
-    storage::{PlanarStorageBindGroup, PlanarStorageLayouts, PlanarStoragePlugin},
+    allocation::PlanarAllocation,
+    auto::{PlanarAutoPlugin, PlanarBackend, PlanarBackendSelection},
+    storage::{
+        PlanarReadOnly, PlanarReadWrite, PlanarStorageAccess, PlanarStorageBindGroup,
+        PlanarStorageLayouts, PlanarStoragePlugin, PlanarStorageShader,
+    },
+    texture::{PlanarTextureBindGroup, PlanarTextureLayouts, PlanarTexturePlugin},
+    validation::PlanarBindingMismatch,
 };
 
-pub use crate::macros::{Planar, ReflectInterleaved, StorageBindings, TextureBindings};
+pub use crate::macros::{
+    PackedStorageBindings, Planar, PlanarEnum, ReflectInterleaved, StorageBindings,
+    TextureBindings,
+};
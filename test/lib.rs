@@ -11,25 +11,304 @@ use bevy_interleave::prelude::*;
     Planar,
     ReflectInterleaved,
     StorageBindings,
-    // TextureBindings,
+    TextureBindings,
 )]
 pub struct MyStruct {
-    // #[texture_format(TextureFormat::R32Sint)]
+    #[texture_format(TextureFormat::R32Sint)]
     pub field: i32,
 
-    // #[texture_format(TextureFormat::R32Uint)]
+    #[texture_format(TextureFormat::R32Uint)]
     pub field2: u32,
 
-    // #[texture_format(TextureFormat::R8Unorm)]
+    #[texture_format(TextureFormat::R8Unorm)]
     pub bool_field: bool,
 
-    // #[texture_format(TextureFormat::Rgba32Uint)]
+    #[texture_format(TextureFormat::Rgba32Uint)]
     pub array: [u32; 4],
 }
 
+#[derive(Clone, Debug, Default, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyBindings {
+    pub position: [f32; 4],
+
+    #[storage(read_write, visibility = compute, binding = 5)]
+    pub velocity: [f32; 4],
+
+    #[storage(visibility = vertex | fragment)]
+    pub mass: f32,
+}
+
+#[derive(Clone, Debug, Default, Reflect, Planar, ReflectInterleaved, PackedStorageBindings)]
+pub struct MyPacked {
+    pub id: u32,
+    pub position: [f32; 3],
+    pub visible: bool,
+    pub color: [f32; 4],
+    pub weights: [f32; 5],
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    PackedStorageBindings,
+)]
+pub struct MyDual {
+    pub position: [f32; 3],
+    pub mass: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyStreams {
+    #[stream(0)]
+    pub position: [f32; 3],
+
+    pub age: f32,
+
+    #[stream(0)]
+    pub alive: bool,
+
+    #[storage(read_write)]
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, Default, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyPadded {
+    pub position: [f32; 3],
+    pub normal: Vec3,
+    pub basis: Mat3,
+    pub scale: Vec3A,
+    pub radius: f32,
+}
+
+#[derive(Clone, Debug, Default, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyFlags {
+    pub id: u32,
+
+    #[storage(bit_packed)]
+    pub selected: bool,
+
+    pub visible: bool,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MyGlam {
+    pub position: Vec3A,
+    pub rotation: Quat,
+    pub transform: Mat4,
+    pub uv: Vec2,
+    pub cell: UVec3,
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    serde::Serialize,
+    serde::Deserialize,
+    PlanarEnum,
+)]
+#[repr(u32)]
+pub enum ParticleKind {
+    #[default]
+    Dust,
+    Spark = 4,
+    Smoke,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MyParticles {
+    pub radius: f32,
+
+    #[planar(enum)]
+    pub kind: ParticleKind,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MySparse {
+    pub position: Vec3,
+    pub normal: Option<Vec3>,
+    pub weight: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyLists {
+    pub id: u32,
+    pub neighbors: Vec<u32>,
+    pub polyline: Vec<Vec3>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyTagged {
+    #[planar(gpu_only)]
+    pub sort_key: u32,
+
+    pub position: Vec3,
+
+    #[planar(cpu_only)]
+    pub label: String,
+
+    #[planar(gpu_only)]
+    #[storage(read_write)]
+    pub velocity: Vec3,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MyLabeled {
+    #[planar(cpu_only)]
+    pub label: String,
+
+    pub value: f32,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MyScalar<T>
+where
+    T: bytemuck::Pod + PlanarWgslType,
+{
+    #[texture_format(TextureFormat::R32Float)]
+    pub value: T,
+
+    pub weight: f32,
+}
+
+#[derive(Clone, Debug, Default, Reflect, Planar, ReflectInterleaved, PackedStorageBindings)]
+pub struct MyPackedPair<T>
+where
+    T: bytemuck::Pod + PlanarWgslType,
+{
+    pub value: T,
+    pub weight: f32,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MySample(
+    pub f32,
+    #[planar(rename = "hits")] pub u32,
+    #[planar(skip)] pub String,
+);
+
+// nested columns follow `id`, e.g. `material.value`
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Reflect,
+    Planar,
+    ReflectInterleaved,
+    StorageBindings,
+    TextureBindings,
+)]
+pub struct MyNested {
+    pub id: u32,
+
+    #[planar(flatten)]
+    pub material: MyLabeled,
+
+    #[planar(flatten, rename = "sample")]
+    pub tuple: MySample,
+}
+
+// more columns than the default `max_storage_buffers_per_shader_stage`
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyWide {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+    pub g: f32,
+    pub h: f32,
+    pub i: f32,
+}
+
+// the same fields interleaved into two bindings
+#[derive(Clone, Debug, Default, PartialEq, Reflect, Planar, ReflectInterleaved, StorageBindings)]
+pub struct MyWideStreamed {
+    #[stream(0)]
+    pub a: f32,
+    #[stream(0)]
+    pub b: f32,
+    #[stream(0)]
+    pub c: f32,
+    #[stream(0)]
+    pub d: f32,
+    #[stream(1)]
+    pub e: f32,
+    #[stream(1)]
+    pub f: f32,
+    #[stream(1)]
+    pub g: f32,
+    #[stream(1)]
+    pub h: f32,
+    pub i: f32,
+}
+
 #[derive(Resource, Default)]
 struct TestSuccess(Arc<Mutex<bool>>);
 
+#[derive(Resource, Default)]
+struct TextureTestSuccess(Arc<Mutex<bool>>);
+
 fn setup_planar(mut commands: Commands, mut gaussian_assets: ResMut<Assets<PlanarMyStruct>>) {
     let planar = PlanarMyStruct::from_interleaved(vec![
         MyStruct {
@@ -58,18 +337,21 @@ fn setup_planar(mut commands: Commands, mut gaussian_assets: ResMut<Assets<Plana
     commands.spawn(PlanarMyStructHandle(planar_handle));
 }
 
-// TODO: require both texture and storage bind groups
-// fn check_texture_bind_group(
-//     bind_group: Query<&PlanarTextureBindGroup::<PlanarTextureMyStruct>>,
-//     success: Res<TestSuccess>,
-// ) {
-//     if bind_group.iter().count() > 0 {
-//         *success.0.lock().unwrap() = true;
-//     }
-// }
+fn check_texture_bind_group(
+    bind_group: Query<&PlanarTextureBindGroup<MyStruct>>,
+    success: Res<TextureTestSuccess>,
+) {
+    if bind_group.iter().count() > 0 {
+        *success.0.lock().unwrap() = true;
+    }
+}
 
+// the same entity carries a bind group per access mode
 fn check_storage_bind_group(
-    bind_group: Query<&PlanarStorageBindGroup<MyStruct>>,
+    bind_group: Query<(
+        &PlanarStorageBindGroup<MyStruct>,
+        &PlanarStorageBindGroup<MyStruct, PlanarReadWrite>,
+    )>,
     success: Res<TestSuccess>,
 ) {
     if bind_group.iter().count() > 0 {
@@ -101,10 +383,10 @@ fn texture_bind_group() {
         DefaultPlugins.set(winit_plugin),
         bevy::app::ScheduleRunnerPlugin::run_loop(std::time::Duration::from_millis(50)),
     ));
-    app.add_plugins(
+    app.add_plugins((
         PlanarStoragePlugin::<MyStruct>::default(),
-        // PlanarTexturePlugin::<PlanarTextureMyStruct>::default(),
-    );
+        PlanarTexturePlugin::<MyStruct>::default(),
+    ));
 
     app.add_systems(Startup, setup_planar);
 
@@ -113,7 +395,7 @@ fn texture_bind_group() {
         bevy::render::Render,
         (
             check_storage_bind_group.in_set(bevy::render::RenderSystems::QueueMeshes),
-            // check_texture_bind_group.in_set(bevy::render::RenderSet::QueueMeshes)
+            check_texture_bind_group.in_set(bevy::render::RenderSystems::QueueMeshes),
         ),
     );
 
@@ -121,10 +403,1949 @@ fn texture_bind_group() {
     let success_arc = success.0.clone();
     render_app.insert_resource(success);
 
+    let texture_success = TextureTestSuccess(Arc::new(Mutex::new(false)));
+    let texture_success_arc = texture_success.0.clone();
+    render_app.insert_resource(texture_success);
+
     app.add_systems(Update, test_timeout);
     app.run();
 
     if !*success_arc.lock().unwrap() {
         panic!("app exit without success flag set - bind group was not found");
     }
+
+    if !*texture_success_arc.lock().unwrap() {
+        panic!("app exit without success flag set - texture bind group was not found");
+    }
+}
+
+fn setup_packed(mut commands: Commands, mut packed_assets: ResMut<Assets<PlanarMyPacked>>) {
+    let planar = PlanarMyPacked::from_interleaved(vec![MyPacked::default(); 3]);
+    commands.spawn(PlanarMyPackedHandle(packed_assets.add(planar)));
+}
+
+fn check_packed_bind_group(
+    bind_group: Query<&PlanarStorageBindGroup<MyPackedPacked>>,
+    success: Res<TestSuccess>,
+) {
+    if bind_group.iter().count() > 0 {
+        *success.0.lock().unwrap() = true;
+    }
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "macos",
+    ignore = "WinitPlugin cannot run on non-main thread on macOS"
+)]
+fn packed_storage_bind_group() {
+    let mut app = App::new();
+
+    let winit_plugin = WinitPlugin {
+        run_on_any_thread: true,
+    };
+
+    app.add_plugins((
+        DefaultPlugins.set(winit_plugin),
+        bevy::app::ScheduleRunnerPlugin::run_loop(std::time::Duration::from_millis(50)),
+    ));
+    app.add_plugins(PlanarStoragePlugin::<MyPackedPacked>::default());
+
+    app.add_systems(Startup, setup_packed);
+
+    let render_app = app.sub_app_mut(bevy::render::RenderApp);
+    render_app.add_systems(
+        bevy::render::Render,
+        check_packed_bind_group.in_set(bevy::render::RenderSystems::QueueMeshes),
+    );
+
+    let success = TestSuccess(Arc::new(Mutex::new(false)));
+    let success_arc = success.0.clone();
+    render_app.insert_resource(success);
+
+    app.add_systems(Update, test_timeout);
+    app.run();
+
+    if !*success_arc.lock().unwrap() {
+        panic!("app exit without success flag set - packed bind group was not found");
+    }
+}
+
+#[test]
+fn planar_texture_layout() {
+    use bevy::render::{render_resource::TextureViewDimension, settings::WgpuLimits};
+    use bevy_interleave::interface::{
+        partition::PlanarLimitError,
+        texture::{
+            check_planar_texture_layers, planar_texture_capacity, planar_texture_data,
+            planar_texture_extent, planar_texture_view_dimension,
+        },
+    };
+
+    let extent = planar_texture_extent(5, 4, TextureFormat::R32Uint);
+    assert_eq!(
+        (extent.width, extent.height, extent.depth_or_array_layers),
+        (3, 3, 1)
+    );
+
+    let extent = planar_texture_extent(0, 12, TextureFormat::R32Float);
+    assert_eq!(
+        (extent.width, extent.height, extent.depth_or_array_layers),
+        (1, 1, 3)
+    );
+
+    // each element spans two texels, the second half lands on the next layer
+    let bytes = [1, 2, 3, 4, 5, 6];
+    let (extent, data) = planar_texture_data(&bytes, 2, TextureFormat::R8Uint);
+    assert_eq!(
+        (extent.width, extent.height, extent.depth_or_array_layers),
+        (2, 2, 2)
+    );
+    assert_eq!(data, vec![1, 3, 5, 0, 2, 4, 6, 0]);
+
+    assert_eq!(
+        planar_texture_view_dimension(4, TextureFormat::R32Sint),
+        TextureViewDimension::D2
+    );
+    assert_eq!(
+        planar_texture_view_dimension(12, TextureFormat::R32Float),
+        TextureViewDimension::D2Array
+    );
+
+    // a 16 byte element spans sixteen r8 layers, more than the two layers of this device
+    let limits = WgpuLimits {
+        max_texture_dimension_2d: 16,
+        max_texture_array_layers: 2,
+        ..Default::default()
+    };
+    assert_eq!(planar_texture_capacity(&limits), 256);
+    assert_eq!(
+        check_planar_texture_layers(8, TextureFormat::R32Uint, &limits),
+        Ok(())
+    );
+    assert_eq!(
+        check_planar_texture_layers(16, TextureFormat::R8Uint, &limits),
+        Err(PlanarLimitError::TextureArrayLayers {
+            required: 16,
+            max: 2
+        })
+    );
+    assert_eq!(
+        <PlanarTextureMyStruct as GpuPlanarTexture>::check_limits(&limits),
+        Ok(())
+    );
+
+    // both backends of `MyStruct` bind one resource per field
+    let field_count = MyStruct::ordered_field_names().len();
+    assert_eq!(
+        PlanarStorageMyStruct::bind_group_layout_entries(true).len(),
+        field_count
+    );
+
+    let entries = PlanarTextureMyStruct::bind_group_layout_entries();
+    assert_eq!(entries.len(), field_count);
+    assert!(matches!(
+        entries[3].ty,
+        bevy::render::render_resource::BindingType::Texture {
+            view_dimension: TextureViewDimension::D2,
+            sample_type: bevy::render::render_resource::TextureSampleType::Uint,
+            ..
+        }
+    ));
+}
+
+#[derive(Resource, Default)]
+struct LifecycleObservations(Arc<Mutex<Vec<Option<bevy::render::render_resource::BindGroupId>>>>);
+
+#[derive(Resource)]
+struct LifecycleHandle(Handle<PlanarMyStruct>);
+
+fn setup_lifecycle(mut commands: Commands, mut planars: ResMut<Assets<PlanarMyStruct>>) {
+    let planar = PlanarMyStruct::from_interleaved(vec![MyStruct::default(); 4]);
+    let handle = planars.add(planar);
+
+    commands.spawn(PlanarMyStructHandle(handle.clone()));
+    commands.insert_resource(LifecycleHandle(handle));
+}
+
+fn drive_lifecycle(
+    mut commands: Commands,
+    mut planars: ResMut<Assets<PlanarMyStruct>>,
+    lifecycle_handle: Res<LifecycleHandle>,
+    entities: Query<Entity, With<PlanarMyStructHandle>>,
+    mut exit: MessageWriter<bevy::app::AppExit>,
+    mut frame_count: Local<u32>,
+) {
+    *frame_count += 1;
+
+    match *frame_count {
+        4 => {
+            let mut planar = planars.get_mut(&lifecycle_handle.0).unwrap();
+            Planar::set(
+                &mut *planar,
+                0,
+                MyStruct {
+                    field: 42,
+                    ..default()
+                },
+            );
+        }
+        8 => {
+            for entity in entities.iter() {
+                commands.entity(entity).remove::<PlanarMyStructHandle>();
+            }
+        }
+        12 => {
+            exit.write(bevy::app::AppExit::Success);
+        }
+        _ => {}
+    }
+}
+
+fn observe_lifecycle(
+    bind_groups: Query<&PlanarStorageBindGroup<MyStruct>>,
+    observations: Res<LifecycleObservations>,
+) {
+    let bind_group_id = bind_groups.iter().next().map(|b| b.bind_groups[0].id());
+    observations.0.lock().unwrap().push(bind_group_id);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "macos",
+    ignore = "WinitPlugin cannot run on non-main thread on macOS"
+)]
+fn storage_bind_group_lifecycle() {
+    let mut app = App::new();
+
+    let winit_plugin = WinitPlugin {
+        run_on_any_thread: true,
+    };
+
+    app.add_plugins((
+        DefaultPlugins.set(winit_plugin),
+        bevy::app::ScheduleRunnerPlugin::run_loop(std::time::Duration::from_millis(50)),
+    ));
+    app.add_plugins(PlanarStoragePlugin::<MyStruct>::default());
+
+    app.add_systems(Startup, setup_lifecycle);
+    app.add_systems(Update, drive_lifecycle);
+
+    let render_app = app.sub_app_mut(bevy::render::RenderApp);
+    render_app.add_systems(
+        bevy::render::Render,
+        observe_lifecycle.in_set(bevy::render::RenderSystems::QueueMeshes),
+    );
+
+    let observations = LifecycleObservations::default();
+    let observations_arc = observations.0.clone();
+    render_app.insert_resource(observations);
+
+    app.run();
+
+    let observations = observations_arc.lock().unwrap();
+
+    let mut distinct_bind_groups = observations.iter().flatten().collect::<Vec<_>>();
+    distinct_bind_groups.dedup();
+    assert!(
+        distinct_bind_groups.len() >= 2,
+        "bind group was not rebuilt after the planar asset was modified"
+    );
+
+    assert_eq!(
+        observations.last(),
+        Some(&None),
+        "bind group was not removed after the planar handle was removed"
+    );
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn dirty_ranges_merge() {
+    use bevy_interleave::interface::dirty::PlanarDirtyRanges;
+
+    let mut dirty = PlanarDirtyRanges::default();
+    assert!(dirty.is_empty());
+
+    dirty.mark(1, 10..12);
+    dirty.mark(1, 2..4);
+    dirty.mark(1, 4..6);
+    dirty.mark(1, 20..20);
+    assert!(dirty.column(0).is_empty());
+    assert_eq!(dirty.column(1), &[2..6, 10..12]);
+
+    dirty.mark(1, 5..11);
+    assert_eq!(dirty.column(1), &[2..12]);
+
+    dirty.clear();
+    assert!(dirty.is_empty());
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn planar_set_marks_dirty() {
+    let mut planar = PlanarMyStruct::from_interleaved(vec![MyStruct::default(); 8]);
+    assert!(planar.dirty_ranges().is_empty());
+
+    Planar::set(&mut planar, 3, MyStruct::default());
+    Planar::set(&mut planar, 4, MyStruct::default());
+    planar.mark_dirty(2, 6..8);
+
+    assert_eq!(planar.dirty_ranges().column(0), &[3..5]);
+    assert_eq!(planar.dirty_ranges().column(2), &[3..5, 6..8]);
+    assert_eq!(planar.dirty_ranges().column(3), &[3..5]);
+
+    assert!(planar.subset(&[0, 3]).dirty_ranges().is_empty());
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn planar_direct_write_marks_dirty() {
+    let mut planar = PlanarMyStruct::from_interleaved(vec![MyStruct::default(); 8]);
+
+    planar.field_mut()[0] = 1;
+    Planar::set(&mut planar, 3, MyStruct::default());
+
+    assert_eq!(planar.field[0], 1);
+    assert_eq!(planar.dirty_ranges().column(0), &[0..8]);
+    assert_eq!(planar.dirty_ranges().column(3), &[0..8]);
+
+    planar.clear_dirty_ranges();
+    planar.field2[7] = 2;
+    planar.mark_all_dirty();
+    Planar::set(&mut planar, 3, MyStruct::default());
+
+    assert_eq!(planar.dirty_ranges().column(1), &[0..8]);
+}
+
+#[test]
+fn dirty_byte_writes() {
+    use bevy_interleave::interface::{
+        dirty::{PlanarDirtyWrite, dirty_writes},
+        paging::PlanarPaging,
+    };
+
+    let write = |offset: usize, bytes: &[u8]| PlanarDirtyWrite {
+        offset,
+        bytes: bytes.to_vec().into(),
+    };
+
+    // aligned rows are written as is
+    let words = (0..20).collect::<Vec<u8>>();
+    assert_eq!(
+        dirty_writes(&words, 4, 20, &[1..2, 3..5]),
+        vec![write(4, &words[4..8]), write(12, &words[12..20])],
+    );
+
+    // unaligned rows are widened to `COPY_BUFFER_ALIGNMENT`, zero padded past the end of the column
+    let rgb = (0..15).collect::<Vec<u8>>();
+    assert_eq!(
+        dirty_writes(&rgb, 3, 16, &[1..2, 4..5]),
+        vec![write(0, &rgb[0..8]), write(12, &[12, 13, 14, 0])],
+    );
+
+    // rows past the column or the page are skipped
+    let rows = |range: std::ops::Range<usize>| vec![range];
+    assert!(dirty_writes(&words, 4, 20, &rows(5..7)).is_empty());
+    assert_eq!(
+        dirty_writes(&words, 4, 8, &rows(1..4)),
+        vec![write(4, &words[4..8])],
+    );
+
+    // dirty rows spanning pages are split relative to each page
+    let paging = PlanarPaging {
+        pages: 2,
+        page_len: 3,
+    };
+    assert_eq!(
+        paging.dirty_writes(&words, 4, 0, 12, &rows(2..4)),
+        vec![write(8, &words[8..12])],
+    );
+    assert_eq!(
+        paging.dirty_writes(&words, 4, 1, 8, &rows(2..4)),
+        vec![write(0, &words[12..16])],
+    );
+}
+
+#[test]
+fn storage_wgsl_source() {
+    assert_eq!(
+        MyStruct::wgsl_struct(),
+        "struct MyStruct {\n    field: i32,\n    field2_: u32,\n    bool_field: bool,\n    array: vec4<u32>,\n}\n",
+    );
+
+    let source = PlanarStorageMyStruct::wgsl_source(1, true);
+    assert!(
+        source.contains("@group(1) @binding(0) var<storage, read> my_struct_field: array<i32>;")
+    );
+    assert!(
+        source.contains(
+            "@group(1) @binding(3) var<storage, read> my_struct_array: array<vec4<u32>>;"
+        )
+    );
+    assert!(source.contains("fn get_my_struct(index: u32) -> MyStruct {"));
+
+    let read_write = PlanarStorageMyStruct::wgsl_source(0, false);
+    assert!(
+        read_write.contains(
+            "@group(0) @binding(1) var<storage, read_write> my_struct_field2_: array<u32>;"
+        )
+    );
+
+    for source in [source, read_write] {
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::default(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+}
+
+#[test]
+fn storage_wgsl_module_import() {
+    let module = PlanarStorageMyStruct::wgsl_module(2, true);
+    let import_path = PlanarStorageMyStruct::wgsl_import_path();
+    assert_eq!(import_path, "bevy_interleave::my_struct::storage");
+
+    let shader = bevy::shader::Shader::from_wgsl(module.clone(), import_path.clone());
+    assert!(matches!(
+        shader.import_path,
+        bevy::shader::ShaderImport::Custom(ref path) if *path == import_path,
+    ));
+
+    let mut composer = naga_oil::compose::Composer::default();
+    composer
+        .add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
+            source: &module,
+            file_path: &import_path,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let user_shader = r#"
+        #import bevy_interleave::my_struct::storage::{get_my_struct, MyStruct}
+
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+            let value: MyStruct = get_my_struct(id.x);
+        }
+    "#;
+
+    let module = composer
+        .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
+            source: user_shader,
+            file_path: "user.wgsl",
+            ..Default::default()
+        })
+        .unwrap();
+
+    let bound_groups = module
+        .global_variables
+        .iter()
+        .filter_map(|(_, global)| global.binding.as_ref().map(|binding| binding.group))
+        .collect::<Vec<_>>();
+    // four columns and the page metadata uniform
+    assert_eq!(bound_groups, vec![2; 5]);
+
+    // compute passes import the read-write module
+    let read_write_path = PlanarStorageMyStruct::wgsl_module_import_path(false);
+    assert_eq!(read_write_path, "bevy_interleave::my_struct::storage_rw");
+
+    composer
+        .add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
+            source: &PlanarStorageMyStruct::wgsl_module(2, false),
+            file_path: &read_write_path,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let module = composer
+        .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
+            source: &user_shader.replace("storage::", "storage_rw::"),
+            file_path: "user_rw.wgsl",
+            ..Default::default()
+        })
+        .unwrap();
+
+    let writable = module
+        .global_variables
+        .iter()
+        .filter(|(_, global)| {
+            matches!(
+                global.space,
+                naga::AddressSpace::Storage { access } if access.contains(naga::StorageAccess::STORE)
+            )
+        })
+        .count();
+    assert_eq!(writable, 4);
+}
+
+#[test]
+fn validate_storage_shader() {
+    let generated =
+        naga::front::wgsl::parse_str(&PlanarStorageMyStruct::wgsl_source(1, true)).unwrap();
+
+    // bool columns are promoted to `u32`
+    assert!(PlanarStorageLayouts::<MyStruct>::validate_shader(&generated, 1).is_empty());
+
+    let drifted = naga::front::wgsl::parse_str(
+        r#"
+        @group(1) @binding(0) var<storage, read_write> field: array<i32>;
+        @group(1) @binding(1) var<storage, read> swapped: array<vec4<u32>>;
+        @group(1) @binding(3) var<uniform> field2: vec4<u32>;
+        @group(1) @binding(7) var<storage, read> extra: array<u32>;
+        @group(0) @binding(2) var<storage, read> other_group: array<u32>;
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        PlanarStorageLayouts::<MyStruct>::validate_shader(&drifted, 1),
+        vec![
+            PlanarBindingMismatch::AddressSpace {
+                binding: 0,
+                field: "field",
+                expected: naga::AddressSpace::Storage {
+                    access: naga::StorageAccess::LOAD,
+                },
+                found: naga::AddressSpace::Storage {
+                    access: naga::StorageAccess::LOAD | naga::StorageAccess::STORE,
+                },
+            },
+            PlanarBindingMismatch::Stride {
+                binding: 1,
+                field: "field2",
+                expected: 4,
+                found: 16,
+            },
+            PlanarBindingMismatch::Missing {
+                binding: 2,
+                field: "bool_field",
+            },
+            PlanarBindingMismatch::AddressSpace {
+                binding: 3,
+                field: "array",
+                expected: naga::AddressSpace::Storage {
+                    access: naga::StorageAccess::LOAD,
+                },
+                found: naga::AddressSpace::Uniform,
+            },
+            PlanarBindingMismatch::Missing {
+                binding: 4,
+                field: "page_metadata",
+            },
+            PlanarBindingMismatch::Unexpected {
+                binding: 7,
+                name: Some("extra".to_string()),
+            },
+        ],
+    );
+}
+
+#[test]
+fn auto_backend_selection() {
+    use bevy::render::{render_resource::DownlevelFlags, settings::WgpuLimits};
+
+    assert_eq!(
+        PlanarBackend::select(&WgpuLimits::default(), DownlevelFlags::all()),
+        PlanarBackend::Storage
+    );
+    assert_eq!(
+        PlanarBackend::select(
+            &WgpuLimits::downlevel_webgl2_defaults(),
+            DownlevelFlags::all()
+        ),
+        PlanarBackend::Texture
+    );
+
+    // storage limits without vertex storage, e.g. some downlevel adapters
+    assert_eq!(
+        PlanarBackend::select(
+            &WgpuLimits::default(),
+            DownlevelFlags::all() - DownlevelFlags::VERTEX_STORAGE
+        ),
+        PlanarBackend::Texture
+    );
+
+    // the storage buffers a planar type needs are checked by `PlanarStoragePlugin`
+    let limits = WgpuLimits {
+        max_storage_buffers_per_shader_stage: 3,
+        ..WgpuLimits::default()
+    };
+    assert_eq!(
+        PlanarBackend::select(&limits, DownlevelFlags::all()),
+        PlanarBackend::Storage
+    );
+
+    assert_eq!(
+        PlanarBackendSelection::<MyStruct>::new(PlanarBackend::Storage).shader_def(),
+        "PLANAR_MY_STRUCT_STORAGE".into(),
+    );
+    assert_eq!(
+        PlanarBackendSelection::<MyStruct>::new(PlanarBackend::Texture).shader_def(),
+        "PLANAR_MY_STRUCT_TEXTURE".into(),
+    );
+    assert_eq!(
+        PlanarBackendSelection::<MyScalar<f32>>::new(PlanarBackend::Storage).shader_def(),
+        "PLANAR_MY_SCALAR_F32_STORAGE".into(),
+    );
+
+    // the configured storage plugin is the one added once storage is selected
+    let auto = PlanarAutoPlugin::<MyStruct>::default()
+        .with_storage(PlanarStoragePlugin::with_bind_group(2).with_max_rows(1 << 20));
+    assert_eq!(auto.storage.bind_group, 2);
+    assert_eq!(auto.storage.clone().max_rows, Some(1 << 20));
+}
+
+fn check_texture_fallback(
+    selection: Res<PlanarBackendSelection<MyStruct>>,
+    texture_bind_groups: Query<&PlanarTextureBindGroup<MyStruct>>,
+    storage_bind_groups: Query<&PlanarStorageBindGroup<MyStruct>>,
+    success: Res<TestSuccess>,
+) {
+    assert_eq!(selection.backend, PlanarBackend::Texture);
+    assert!(storage_bind_groups.is_empty());
+
+    if texture_bind_groups.iter().count() > 0 {
+        *success.0.lock().unwrap() = true;
+    }
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "macos",
+    ignore = "WinitPlugin cannot run on non-main thread on macOS"
+)]
+fn auto_texture_fallback() {
+    let mut app = App::new();
+
+    let winit_plugin = WinitPlugin {
+        run_on_any_thread: true,
+    };
+
+    app.add_plugins((
+        DefaultPlugins.set(winit_plugin),
+        bevy::app::ScheduleRunnerPlugin::run_loop(std::time::Duration::from_millis(50)),
+    ));
+    app.add_plugins(PlanarAutoPlugin::<MyStruct>::with_limits(
+        bevy::render::settings::WgpuLimits::downlevel_webgl2_defaults(),
+    ));
+
+    app.add_systems(Startup, setup_planar);
+
+    let render_app = app.sub_app_mut(bevy::render::RenderApp);
+    render_app.add_systems(
+        bevy::render::Render,
+        check_texture_fallback.in_set(bevy::render::RenderSystems::QueueMeshes),
+    );
+
+    let success = TestSuccess(Arc::new(Mutex::new(false)));
+    let success_arc = success.0.clone();
+    render_app.insert_resource(success);
+
+    app.add_systems(Update, test_timeout);
+    app.run();
+
+    if !*success_arc.lock().unwrap() {
+        panic!("app exit without success flag set - texture fallback bind group was not found");
+    }
+}
+
+#[test]
+fn storage_partition() {
+    use bevy::render::settings::WgpuLimits;
+    use bevy_interleave::interface::{
+        paging::PlanarPaging,
+        partition::{PlanarBindingLocation, PlanarPartition},
+    };
+
+    let field_count = MyStruct::ordered_field_names().len();
+    assert_eq!(
+        PlanarPartition::from_limits(1, field_count, &WgpuLimits::default()),
+        PlanarPartition::single(1, field_count),
+    );
+
+    let partition = PlanarPartition::new(1, field_count, 3);
+    assert_eq!(partition.groups, vec![0..3, 3..4]);
+    assert_eq!(partition.group_count(), 2);
+    assert_eq!(partition.field_count(), field_count);
+    assert_eq!(
+        partition.location(2),
+        PlanarBindingLocation {
+            group: 1,
+            binding: 2
+        }
+    );
+    assert_eq!(
+        partition.location(3),
+        PlanarBindingLocation {
+            group: 2,
+            binding: 0
+        }
+    );
+
+    let split = partition.split(
+        PlanarStorageMyStruct::bind_group_layout_entries(true),
+        |entry, binding| entry.binding = binding,
+    );
+    assert_eq!(
+        split
+            .iter()
+            .map(|entries| entries.iter().map(|entry| entry.binding).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        vec![vec![0, 1, 2], vec![0]],
+    );
+
+    let source = PlanarStorageMyStruct::wgsl_partitioned_source(
+        &partition,
+        &PlanarPaging::default(),
+        true,
+    );
+    assert!(
+        source.contains("@group(1) @binding(2) var<storage, read> my_struct_bool_field: array<u32>;")
+    );
+    assert!(
+        source.contains("@group(2) @binding(0) var<storage, read> my_struct_array: array<vec4<u32>>;")
+    );
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    assert!(
+        PlanarStorageLayouts::<MyStruct>::validate_partitioned_shader(
+            &module,
+            &partition,
+            &PlanarPaging::default(),
+        )
+        .is_empty()
+    );
+
+    // the single group layout no longer matches
+    assert!(
+        PlanarStorageLayouts::<MyStruct>::validate_shader(&module, 1)
+            .contains(&PlanarBindingMismatch::Missing {
+                binding: 3,
+                field: "array",
+            })
+    );
+}
+
+#[test]
+fn storage_limits() {
+    use bevy::render::{render_resource::ShaderStages, settings::WgpuLimits};
+    use bevy_interleave::interface::{
+        paging::PlanarPaging,
+        partition::{PlanarLimitError, PlanarPartition},
+    };
+
+    let limits = WgpuLimits::default();
+    let check = |entries: Vec<_>, pages: u32| {
+        let paging = PlanarPaging {
+            pages,
+            ..PlanarPaging::default()
+        };
+        let entries = paging.expand(entries);
+
+        PlanarPartition::from_limits(0, entries.len(), &limits).check_limits(&entries, &limits)
+    };
+
+    // bind groups do not help, every group of the pipeline counts against the per stage limit
+    assert_eq!(
+        check(PlanarStorageMyWide::bind_group_layout_entries(true), 1),
+        Err(PlanarLimitError::StorageBuffersPerStage {
+            stage: ShaderStages::VERTEX,
+            required: 9,
+            max: 8,
+        }),
+    );
+    assert_eq!(
+        PlanarPartition::from_limits(0, 9, &limits),
+        PlanarPartition::single(0, 9),
+    );
+
+    // read-write columns are only visible to the compute stage unless their visibility says otherwise
+    let mut entries = PlanarStorageMyWide::bind_group_layout_entries(false);
+    assert_eq!(
+        check(entries.clone(), 1),
+        Err(PlanarLimitError::StorageBuffersPerStage {
+            stage: ShaderStages::COMPUTE,
+            required: 9,
+            max: 8,
+        }),
+    );
+    entries[0].visibility = ShaderStages::FRAGMENT;
+    assert_eq!(check(entries, 1), Ok(()));
+
+    assert_eq!(
+        check(PlanarStorageMyWideStreamed::bind_group_layout_entries(true), 1),
+        Ok(()),
+    );
+    assert_eq!(
+        check(PlanarStorageMyWideStreamed::bind_group_layout_entries(true), 3),
+        Err(PlanarLimitError::StorageBuffersPerStage {
+            stage: ShaderStages::VERTEX,
+            required: 9,
+            max: 8,
+        }),
+    );
+
+    let entries = PlanarStorageMyWideStreamed::bind_group_layout_entries(true);
+    assert_eq!(
+        PlanarPartition::new(3, entries.len(), 1).check_limits(&entries, &limits),
+        Err(PlanarLimitError::BindGroups {
+            required: 6,
+            max: 4,
+        }),
+    );
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn storage_sharing() {
+    use bevy::render::{render_resource::ShaderStages, settings::WgpuLimits};
+    use bevy_interleave::interface::{
+        allocation::{PlanarColumnUpload, PlanarSharedUpload},
+        paging::entry_stride,
+        partition::{PlanarLimitError, PlanarSharing},
+    };
+
+    // the surplus column of `MyWide` is interleaved with the last column of the same type
+    let limits = WgpuLimits::default();
+    let (partition, paging) = PlanarStoragePlugin::<MyWide>::default()
+        .layout(&limits)
+        .unwrap();
+    assert_eq!(
+        partition.sharing,
+        PlanarSharing {
+            bindings: vec![vec![7, 8]],
+        },
+    );
+    assert_eq!(partition.field_count(), 8);
+    assert_eq!(partition.sharing.lane(9, 8), (7, 1, 2));
+
+    let entries = partition
+        .sharing
+        .entries(&PlanarStorageMyWide::bind_group_layout_entries(true));
+    assert_eq!(entries.len(), 8);
+    assert_eq!(entry_stride(&entries[6]), 4);
+    assert_eq!(entry_stride(&entries[7]), 8);
+    assert!(partition.check_limits(&entries, &limits).is_ok());
+
+    let source = PlanarStorageMyWide::wgsl_partitioned_source(&partition, &paging, true);
+    assert!(source.contains("@group(0) @binding(7) var<storage, read> my_wide_h: array<f32>;"));
+    assert!(!source.contains("var<storage, read> my_wide_i:"));
+    assert!(source.contains("default: { return my_wide_h[page.y * 2u + 1u]; }"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(
+        PlanarStorageLayouts::<MyWide>::validate_partitioned_shader(&module, &partition, &paging)
+            .is_empty()
+    );
+
+    // every page counts, two pages per column leave room for four bindings
+    let (partition, _) = PlanarStoragePlugin::<MyWide>::default()
+        .with_pages(2)
+        .layout(&limits)
+        .unwrap();
+    assert_eq!(partition.sharing.bindings, vec![(3..9).collect::<Vec<_>>()]);
+    assert_eq!(partition.field_count(), 8);
+
+    // streams of different structs can not share a binding
+    assert_eq!(
+        PlanarStoragePlugin::<MyWideStreamed>::default()
+            .with_pages(3)
+            .layout(&limits),
+        Err(PlanarLimitError::StorageBuffersPerStage {
+            stage: ShaderStages::VERTEX,
+            required: 9,
+            max: 8,
+        }),
+    );
+
+    // rows of both lanes are interleaved, rows dirty in either lane are written
+    let column = |bytes, rows| PlanarColumnUpload {
+        label: "column",
+        bytes,
+        stride: 4,
+        rows,
+        bit_packed: false,
+    };
+    let shared = PlanarSharedUpload::new(&[
+        column(bytemuck::cast_slice(&[1u32, 2, 3]), &[0..1]),
+        column(bytemuck::cast_slice(&[4u32, 5, 6]), &[2..3]),
+    ]);
+    assert_eq!(
+        bytemuck::cast_slice::<u8, u32>(&shared.bytes),
+        &[1, 4, 2, 5, 3, 6]
+    );
+    assert_eq!(shared.rows, vec![0..1, 2..3]);
+
+    // columns within the limits are bound as declared
+    let (partition, _) = PlanarStoragePlugin::<MyStruct>::default()
+        .layout(&limits)
+        .unwrap();
+    assert!(partition.sharing.is_empty());
+}
+
+#[test]
+fn storage_paging() {
+    use bevy::render::settings::WgpuLimits;
+    use bevy_interleave::interface::{
+        paging::{PlanarPageMetadata, PlanarPaging},
+        partition::PlanarPartition,
+    };
+
+    assert_eq!(
+        PlanarPaging::from_layout_entries(
+            1,
+            &PlanarStorageMyStruct::bind_group_layout_entries(true),
+            &WgpuLimits::default(),
+        ),
+        PlanarPaging {
+            pages: 1,
+            page_len: (WgpuLimits::default().max_storage_buffer_binding_size / 16) as usize,
+        },
+    );
+
+    // the widest column bounds the page length
+    let paging = PlanarPaging::new(2, 64, MyStruct::min_binding_sizes());
+    assert_eq!(paging.page_len, 4);
+    assert_eq!(paging.capacity(), 8);
+    assert_eq!(paging.page_count(5), 2);
+    assert_eq!(paging.page_count(20), 2);
+    assert_eq!(paging.page_rows(5, 0), 0..4);
+    assert_eq!(paging.page_rows(5, 1), 4..5);
+    assert_eq!(paging.page_ranges(1, &[1..2, 3..6]), vec![0..2]);
+    assert_eq!(paging.expand(vec!["a", "b"]), vec!["a", "a", "b", "b"]);
+    assert_eq!(paging.truncated(5), 0);
+    assert_eq!(paging.truncated(20), 12);
+
+    // pages for an expected row count, never fewer than requested
+    assert_eq!(paging.with_capacity(20).pages, 5);
+    assert_eq!(paging.with_capacity(20).truncated(20), 0);
+    assert_eq!(paging.with_capacity(3).pages, 2);
+
+    assert_eq!(
+        paging.metadata(5),
+        PlanarPageMetadata {
+            count: 5,
+            page_count: 2,
+            page_len: 4,
+        },
+    );
+    assert_eq!(
+        paging.metadata(5).to_bytes(),
+        [5, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0],
+    );
+
+    let field_count = MyStruct::ordered_field_names().len();
+    let partition = PlanarPartition::single(1, field_count * paging.pages as usize);
+    let source = PlanarStorageMyStruct::wgsl_partitioned_source(&partition, &paging, true);
+    assert!(
+        source.contains("@group(1) @binding(1) var<storage, read> my_struct_field_page1_: array<i32>;")
+    );
+    assert!(
+        source.contains("@group(1) @binding(8) var<uniform> my_struct_paging: MyStructPages;")
+    );
+    assert!(source.contains("fn load_my_struct_field2_(index: u32) -> u32 {"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    assert!(
+        PlanarStorageLayouts::<MyStruct>::validate_partitioned_shader(&module, &partition, &paging)
+            .is_empty()
+    );
+}
+
+#[test]
+fn storage_field_attributes() {
+    use bevy::render::render_resource::{BindingType, BufferBindingType, ShaderStages};
+
+    let entries = PlanarStorageMyBindings::bind_group_layout_entries(true);
+    assert_eq!(
+        entries.iter().map(|entry| entry.binding).collect::<Vec<_>>(),
+        vec![0, 5, 6],
+    );
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.visibility)
+            .collect::<Vec<_>>(),
+        vec![
+            ShaderStages::VERTEX_FRAGMENT | ShaderStages::COMPUTE,
+            ShaderStages::COMPUTE,
+            ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+        ],
+    );
+
+    let read_only = |entry: &bevy::render::render_resource::BindGroupLayoutEntry| match entry.ty {
+        BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            ..
+        } => read_only,
+        _ => panic!("expected a storage buffer"),
+    };
+    assert_eq!(
+        entries.iter().map(read_only).collect::<Vec<_>>(),
+        vec![true, false, true],
+    );
+
+    // fields without an access attribute follow the argument, writable storage is compute only by default
+    let read_write = PlanarStorageMyBindings::bind_group_layout_entries(false);
+    assert_eq!(
+        read_write.iter().map(read_only).collect::<Vec<_>>(),
+        vec![false, false, false],
+    );
+    assert_eq!(read_write[0].visibility, ShaderStages::COMPUTE);
+    assert!(!bevy_interleave::interface::storage::writes_from_fragment(&read_write[..2]));
+    assert!(bevy_interleave::interface::storage::writes_from_fragment(&read_write));
+
+    let source = PlanarStorageMyBindings::wgsl_source(1, true);
+    assert!(
+        source.contains(
+            "@group(1) @binding(5) var<storage, read_write> my_bindings_velocity: array<vec4<f32>>;"
+        )
+    );
+    assert!(source.contains("@group(1) @binding(6) var<storage, read> my_bindings_mass: array<f32>;"));
+    assert!(source.contains("@group(1) @binding(7) var<uniform> my_bindings_paging: MyBindingsPages;"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyBindings>::validate_shader(&module, 1).is_empty());
+}
+
+#[test]
+fn packed_storage_layout() {
+    // std430 rather than `size_of`, e.g. `vec3<f32>` is 16 byte aligned and `bool` is widened to `u32`
+    assert_eq!(PackedStorageMyPacked::STD430_OFFSETS, &[0, 16, 28, 32, 48]);
+    assert_eq!(PackedStorageMyPacked::STD430_STRIDE, 80);
+
+    let planar = PlanarMyPacked::from_interleaved(vec![
+        MyPacked {
+            id: 7,
+            position: [1.0, 2.0, 3.0],
+            visible: true,
+            color: [0.5; 4],
+            weights: [4.0; 5],
+        },
+        MyPacked::default(),
+    ]);
+
+    let bytes = PackedStorageMyPacked::std430_bytes(&planar);
+    assert_eq!(bytes.len(), 2 * PackedStorageMyPacked::STD430_STRIDE);
+    assert_eq!(bytemuck::cast_slice::<u8, u32>(&bytes[0..4]), &[7]);
+    assert_eq!(
+        bytemuck::cast_slice::<u8, f32>(&bytes[16..28]),
+        &[1.0, 2.0, 3.0]
+    );
+    assert_eq!(bytemuck::cast_slice::<u8, u32>(&bytes[28..32]), &[1]);
+    assert_eq!(bytemuck::cast_slice::<u8, f32>(&bytes[32..48]), &[0.5; 4]);
+    assert_eq!(bytemuck::cast_slice::<u8, f32>(&bytes[48..68]), &[4.0; 5]);
+    assert!(bytes[4..16].iter().all(|byte| *byte == 0));
+    assert!(bytes[80..].iter().all(|byte| *byte == 0));
+
+    let entries = PackedStorageMyPacked::bind_group_layout_entries(true);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        bevy_interleave::interface::paging::entry_stride(&entries[0]),
+        PackedStorageMyPacked::STD430_STRIDE,
+    );
+
+    let source = PackedStorageMyPacked::wgsl_source(1, true);
+    assert!(source.contains(
+        "@group(1) @binding(0) var<storage, read> my_packed_packed: array<MyPackedPacked>;"
+    ));
+    assert!(source.contains("fn get_my_packed(index: u32) -> MyPacked {"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // naga agrees with the generated layout
+    let (_, storage) = module
+        .types
+        .iter()
+        .find(|(_, ty)| ty.name.as_deref() == Some("MyPackedPacked"))
+        .unwrap();
+    let naga::TypeInner::Struct { members, span } = &storage.inner else {
+        panic!("expected a struct");
+    };
+    assert_eq!(
+        members
+            .iter()
+            .map(|member| member.offset as usize)
+            .collect::<Vec<_>>(),
+        PackedStorageMyPacked::STD430_OFFSETS,
+    );
+    assert_eq!(*span as usize, PackedStorageMyPacked::STD430_STRIDE);
+
+    assert!(PlanarStorageLayouts::<MyPackedPacked>::validate_shader(&module, 1).is_empty());
+}
+
+#[test]
+fn packed_storage_marker() {
+    fn gpu_storage<R: PlanarStorage>() -> &'static str {
+        std::any::type_name::<R::GpuStorageType>()
+    }
+
+    // both storage derives on one type, the packed backend is selected through `<Name>Packed`
+    assert!(gpu_storage::<MyDual>().ends_with("::PlanarStorageMyDual"));
+    assert!(gpu_storage::<MyDualPacked>().ends_with("::PackedStorageMyDual"));
+
+    let planar = PlanarMyDual::from_interleaved(vec![MyDual::default(); 2]);
+    assert_eq!(PackedStorageMyDual::std430_bytes(&planar).len(), 32);
+}
+
+#[test]
+fn storage_streams() {
+    // `position` and `alive` share one interleaved binding at the position of the first stream field
+    let entries = PlanarStorageMyStreams::bind_group_layout_entries(true);
+    assert_eq!(
+        entries.iter().map(|entry| entry.binding).collect::<Vec<_>>(),
+        vec![0, 1, 2],
+    );
+    assert_eq!(
+        entries
+            .iter()
+            .map(bevy_interleave::interface::paging::entry_stride)
+            .collect::<Vec<_>>(),
+        vec![16, 4, 16],
+    );
+    assert_eq!(
+        PlanarStorageMyStreams::binding_names(),
+        &["stream0", "age", "color"]
+    );
+
+    // streamed fields report the stride of their shared binding
+    assert_eq!(MyStreams::min_binding_sizes(), &[16, 4, 16, 16]);
+
+    let source = PlanarStorageMyStreams::wgsl_source(1, true);
+    assert!(source.contains(
+        "@group(1) @binding(0) var<storage, read> my_streams_stream0_: array<MyStreamsStream0_>;"
+    ));
+    assert!(source.contains("@group(1) @binding(1) var<storage, read> my_streams_age: array<f32>;"));
+    assert!(
+        source.contains(
+            "@group(1) @binding(2) var<storage, read_write> my_streams_color: array<vec4<f32>>;"
+        )
+    );
+    assert!(source.contains("    let stream0_ = load_my_streams_stream0_(index);\n"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyStreams>::validate_shader(&module, 1).is_empty());
+
+    // streams only change the gpu layout, the planar asset keeps one column per field
+    let packed = vec![
+        MyStreams {
+            position: [1.0, 2.0, 3.0],
+            age: 4.0,
+            alive: true,
+            color: [0.5; 4],
+        },
+        MyStreams::default(),
+    ];
+    let planar = PlanarMyStreams::from_interleaved(packed.clone());
+    assert_eq!(planar.alive, vec![true, false]);
+    assert_eq!(planar.to_interleaved(), packed);
+
+    // a stream is rewritten where any of its fields changed
+    let mut dirty = bevy_interleave::interface::dirty::PlanarDirtyRanges::default();
+    dirty.mark(0, 0..2);
+    dirty.mark(1, 4..6);
+    dirty.mark(2, 1..3);
+    assert_eq!(dirty.union(&[0, 2]), vec![0..3]);
+    assert_eq!(dirty.rows(), vec![0..3, 4..6]);
+}
+
+#[test]
+fn single_allocation_layout() {
+    use bevy_interleave::interface::{allocation::PlanarAllocationLayout, paging::PlanarPaging};
+
+    // columns start at aligned offsets, sizes are padded to `COPY_BUFFER_ALIGNMENT`
+    let layout = PlanarAllocationLayout::new(10, &[4, 16, 1], &PlanarPaging::default(), 256);
+    assert_eq!(layout.pages, vec![0..40, 256..416, 512..524]);
+    assert_eq!(layout.size, 524);
+
+    // pages of a column are consecutive bindings
+    let paging = PlanarPaging {
+        pages: 2,
+        page_len: 8,
+    };
+    let layout = PlanarAllocationLayout::new(10, &[4, 16], &paging, 256);
+    assert_eq!(layout.pages, vec![0..32, 256..264, 512..640, 768..800]);
+    assert_eq!(layout.size, 800);
+
+    // empty columns still bind one element
+    let layout = PlanarAllocationLayout::new(0, &[16, 4], &PlanarPaging::default(), 64);
+    assert_eq!(layout.pages, vec![0..16, 64..68]);
+}
+
+#[test]
+fn padded_storage_columns() {
+    // `vec3<f32>` elements are 16 bytes apart in a wgsl array, `mat3x3<f32>` columns too
+    assert_eq!(MyPadded::min_binding_sizes(), &[16, 16, 48, 16, 4]);
+
+    let entries = PlanarStorageMyPadded::bind_group_layout_entries(true);
+    assert_eq!(
+        entries
+            .iter()
+            .map(bevy_interleave::interface::paging::entry_stride)
+            .collect::<Vec<_>>(),
+        vec![16, 16, 48, 16, 4],
+    );
+
+    let source = PlanarStorageMyPadded::wgsl_source(1, true);
+    assert!(source.contains(
+        "@group(1) @binding(2) var<storage, read> my_padded_basis: array<mat3x3<f32>>;"
+    ));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+
+    // naga agrees with the padded strides
+    assert!(PlanarStorageLayouts::<MyPadded>::validate_shader(&module, 1).is_empty());
+}
+
+#[test]
+fn bool_storage_columns() {
+    use bevy_interleave::interface::paging::PlanarPaging;
+
+    // promoted to `u32` by default, bit-packed columns bind whole words
+    assert_eq!(MyStruct::min_binding_sizes(), &[4, 4, 4, 16]);
+    assert_eq!(
+        PlanarStorageMyFlags::bind_group_layout_entries(true)
+            .iter()
+            .map(bevy_interleave::interface::paging::entry_stride)
+            .collect::<Vec<_>>(),
+        vec![4, 4, 4],
+    );
+
+    let source = PlanarStorageMyFlags::wgsl_source(1, true);
+    assert!(source.contains("@group(1) @binding(1) var<storage, read> my_flags_selected: array<u32>;"));
+    assert!(source.contains("return (my_flags_selected[page.y / 32u] >> (page.y % 32u)) & 1u;"));
+    assert!(source.contains(
+        "fn get_my_flags_selected(index: u32) -> bool {\n    return load_my_flags_selected(index) != 0u;\n}"
+    ));
+    assert!(source.contains("fn get_my_flags_visible(index: u32) -> bool {"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyFlags>::validate_shader(&module, 1).is_empty());
+
+    // each page packs its own rows from the lowest bit of its first word
+    let paging = PlanarPaging {
+        pages: 2,
+        page_len: 40,
+    };
+    let bits = (0..50).map(|row| row % 3 == 0).collect::<Vec<_>>();
+    let words = paging.pack_bits(&bits);
+    assert_eq!(words.len(), 3);
+    for (row, bit) in bits.iter().enumerate() {
+        let (page, offset) = (row / 40, row % 40);
+        let word = words[page * 2 + offset / 32];
+        assert_eq!((word >> (offset % 32)) & 1 == 1, *bit);
+    }
+
+    let rows = std::iter::once(35..45).collect::<Vec<_>>();
+    assert_eq!(paging.bit_packed_rows(&rows), vec![1..2, 2..3]);
+}
+
+#[test]
+fn glam_fields() {
+    use bevy::render::render_resource::{BindingType, TextureSampleType, TextureViewDimension};
+
+    assert_eq!(
+        MyGlam::wgsl_struct(),
+        "struct MyGlam {\n    position: vec3<f32>,\n    rotation: vec4<f32>,\n    transform: mat4x4<f32>,\n    uv: vec2<f32>,\n    cell: vec3<u32>,\n}\n",
+    );
+    assert_eq!(MyGlam::min_binding_sizes(), &[16, 16, 64, 8, 16]);
+
+    let source = PlanarStorageMyGlam::wgsl_source(0, true);
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyGlam>::validate_shader(&module, 0).is_empty());
+
+    // default texture formats, `Mat4` spans four `Rgba32Float` layers
+    let texture = |entry: &bevy::render::render_resource::BindGroupLayoutEntry| match entry.ty {
+        BindingType::Texture {
+            view_dimension,
+            sample_type,
+            ..
+        } => (view_dimension, sample_type),
+        _ => panic!("expected a texture"),
+    };
+    let float = TextureSampleType::Float { filterable: false };
+    assert_eq!(
+        PlanarTextureMyGlam::bind_group_layout_entries()
+            .iter()
+            .map(texture)
+            .collect::<Vec<_>>(),
+        vec![
+            (TextureViewDimension::D2, float),
+            (TextureViewDimension::D2, float),
+            (TextureViewDimension::D2Array, float),
+            (TextureViewDimension::D2, float),
+            (TextureViewDimension::D2, TextureSampleType::Uint),
+        ],
+    );
+
+    let packed = vec![
+        MyGlam {
+            position: Vec3A::new(1.0, 2.0, 3.0),
+            rotation: Quat::from_rotation_z(1.0),
+            transform: Mat4::from_scale(Vec3::splat(2.0)),
+            uv: Vec2::new(0.5, 0.25),
+            cell: UVec3::new(1, 2, 3),
+        },
+        MyGlam::default(),
+    ];
+    let planar = PlanarMyGlam::from_interleaved(packed.clone());
+    assert_eq!(planar.to_interleaved(), packed);
+}
+
+#[test]
+fn enum_fields() {
+    use bevy::render::render_resource::{BindingType, TextureSampleType};
+
+    assert_eq!(
+        MyParticles::wgsl_struct(),
+        "struct MyParticles {\n    radius: f32,\n    kind: u32,\n}\n",
+    );
+    assert_eq!(MyParticles::min_binding_sizes(), &[4, 4]);
+    assert_eq!(ParticleKind::Spark.discriminant(), 4);
+    assert_eq!(
+        ParticleKind::wgsl_consts(),
+        "const PARTICLE_KIND_DUST: u32 = 0u;\nconst PARTICLE_KIND_SPARK: u32 = 4u;\nconst PARTICLE_KIND_SMOKE: u32 = 5u;\n",
+    );
+
+    let source = PlanarStorageMyParticles::wgsl_source(0, true);
+    assert!(source.contains(&ParticleKind::wgsl_consts()));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyParticles>::validate_shader(&module, 0).is_empty());
+
+    assert!(matches!(
+        PlanarTextureMyParticles::bind_group_layout_entries()[1].ty,
+        BindingType::Texture {
+            sample_type: TextureSampleType::Uint,
+            ..
+        },
+    ));
+
+    let packed = vec![
+        MyParticles {
+            radius: 1.0,
+            kind: ParticleKind::Smoke,
+        },
+        MyParticles::default(),
+    ];
+    let planar = PlanarMyParticles::from_interleaved(packed.clone());
+    assert_eq!(planar.to_interleaved(), packed);
+    assert_eq!(
+        bytemuck::cast_slice::<_, u32>(planar.kind.as_slice()),
+        &[5, 0],
+    );
+}
+
+#[test]
+fn optional_fields() {
+    use bevy_interleave::interface::paging::PlanarPaging;
+
+    let packed = vec![
+        MySparse {
+            position: Vec3::X,
+            normal: Some(Vec3::Y),
+            weight: None,
+        },
+        MySparse {
+            position: Vec3::Y,
+            normal: None,
+            weight: Some(2.0),
+        },
+        MySparse::default(),
+    ];
+
+    // dense values, `None` rows hold the default value
+    let mut planar = PlanarMySparse::from_interleaved(packed.clone());
+    assert_eq!(planar.normal, vec![Vec3::Y, Vec3::ZERO, Vec3::ZERO]);
+    assert_eq!(planar.normal_validity.words(), &[0b001]);
+    assert_eq!(planar.weight_validity.words(), &[0b010]);
+    assert_eq!(planar.to_interleaved(), packed);
+
+    Planar::set(
+        &mut planar,
+        2,
+        MySparse {
+            position: Vec3::Z,
+            normal: Some(Vec3::Z),
+            weight: None,
+        },
+    );
+    assert_eq!(planar.get(2).normal, Some(Vec3::Z));
+    assert_eq!(planar.normal_validity.words(), &[0b101]);
+
+    let subset = planar.subset(&[2, 1]);
+    assert_eq!(subset.get(0).normal, Some(Vec3::Z));
+    assert_eq!(subset.get(1).normal, None);
+    assert_eq!(subset.get(1).weight, Some(2.0));
+
+    // values and validity bitmasks are separate bindings
+    assert_eq!(
+        PlanarStorageMySparse::binding_names(),
+        &["position", "normal", "normal_validity", "weight", "weight_validity"],
+    );
+    assert_eq!(
+        PlanarStorageMySparse::bind_group_layout_entries(true)
+            .iter()
+            .map(bevy_interleave::interface::paging::entry_stride)
+            .collect::<Vec<_>>(),
+        vec![16, 16, 4, 4, 4],
+    );
+
+    let source = PlanarStorageMySparse::wgsl_source(0, true);
+    assert!(source.contains(
+        "fn has_my_sparse_normal(index: u32) -> bool {\n    return load_my_sparse_normal_validity(index) != 0u;\n}"
+    ));
+    assert!(source.contains("fn get_my_sparse_normal(index: u32) -> vec3<f32> {"));
+    assert!(source.contains("fn has_my_sparse_weight(index: u32) -> bool {"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MySparse>::validate_shader(&module, 0).is_empty());
+
+    // validity bitmasks are repacked per page like bit-packed bools
+    let paging = PlanarPaging {
+        pages: 2,
+        page_len: 2,
+    };
+    assert_eq!(paging.pack_validity(&planar.normal_validity), vec![0b01, 0b1]);
+}
+
+#[test]
+fn list_fields() {
+    use bevy_interleave::interface::list::list_value_rows;
+
+    let packed = vec![
+        MyLists {
+            id: 0,
+            neighbors: vec![1, 2],
+            polyline: vec![Vec3::X],
+        },
+        MyLists {
+            id: 1,
+            neighbors: vec![],
+            polyline: vec![Vec3::Y, Vec3::Z],
+        },
+        MyLists {
+            id: 2,
+            neighbors: vec![0, 1, 3],
+            polyline: vec![],
+        },
+    ];
+
+    // flat values and an end offset per row
+    let mut planar = PlanarMyLists::from_interleaved(packed.clone());
+    assert_eq!(planar.len(), 3);
+    assert_eq!(planar.neighbors, vec![1, 2, 0, 1, 3]);
+    assert_eq!(planar.neighbors_offsets, vec![2, 2, 5]);
+    assert_eq!(planar.to_interleaved(), packed);
+
+    // resizing a list moves the values of later rows
+    Planar::set(
+        &mut planar,
+        1,
+        MyLists {
+            id: 1,
+            neighbors: vec![4],
+            polyline: vec![Vec3::ONE, Vec3::ONE],
+        },
+    );
+    assert_eq!(planar.neighbors, vec![1, 2, 4, 0, 1, 3]);
+    assert_eq!(planar.neighbors_offsets, vec![2, 3, 6]);
+    assert_eq!(planar.get(2).neighbors, vec![0, 1, 3]);
+    assert_eq!(
+        planar.dirty_ranges.column(1),
+        std::iter::once(1..3).collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        planar.dirty_ranges.column(2),
+        std::iter::once(1..2).collect::<Vec<_>>(),
+    );
+    assert_eq!(
+        list_value_rows(&planar.neighbors_offsets, planar.dirty_ranges.column(1)),
+        std::iter::once(2..6).collect::<Vec<_>>(),
+    );
+
+    let subset = planar.subset(&[2, 0]);
+    assert_eq!(subset.neighbors, vec![0, 1, 3, 1, 2]);
+    assert_eq!(subset.neighbors_offsets, vec![3, 5]);
+    assert_eq!(subset.get(1).polyline, vec![Vec3::X]);
+
+    // values are paged by value index, offsets by row
+    assert_eq!(
+        PlanarStorageMyLists::binding_names(),
+        &["id", "neighbors", "neighbors_offsets", "polyline", "polyline_offsets"],
+    );
+    assert_eq!(
+        PlanarStorageMyLists::bind_group_layout_entries(true)
+            .iter()
+            .map(bevy_interleave::interface::paging::entry_stride)
+            .collect::<Vec<_>>(),
+        vec![4, 4, 4, 16, 4],
+    );
+    assert_eq!(
+        MyLists::wgsl_struct(),
+        "struct MyLists {\n    id: u32,\n    neighbors: vec2<u32>,\n    polyline: vec2<u32>,\n}\n",
+    );
+
+    let source = PlanarStorageMyLists::wgsl_source(0, true);
+    assert!(source.contains("fn get_my_lists_neighbors_range(index: u32) -> vec2<u32> {"));
+    assert!(source.contains("return vec2<u32>(load_my_lists_neighbors_offsets(index - 1u), end);"));
+    assert!(source.contains("fn get_my_lists_polyline(index: u32) -> vec3<f32> {"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyLists>::validate_shader(&module, 0).is_empty());
+}
+
+#[test]
+fn cpu_and_gpu_only_fields() {
+    let packed = vec![
+        MyTagged {
+            sort_key: 7,
+            position: Vec3::X,
+            label: "first".to_string(),
+            velocity: Vec3::ONE,
+        },
+        MyTagged {
+            position: Vec3::Y,
+            label: "second".to_string(),
+            ..default()
+        },
+    ];
+
+    // gpu-only fields have no CPU column and read back as their default
+    let planar = PlanarMyTagged::from_interleaved(packed);
+    assert_eq!(planar.len(), 2);
+    assert_eq!(planar.label, vec!["first".to_string(), "second".to_string()]);
+    assert_eq!(
+        planar.get(0),
+        MyTagged {
+            position: Vec3::X,
+            label: "first".to_string(),
+            ..default()
+        },
+    );
+    assert_eq!(planar.subset(&[1]).label, vec!["second".to_string()]);
+
+    // cpu-only fields are never uploaded
+    assert_eq!(
+        MyTagged::wgsl_struct(),
+        "struct MyTagged {\n    sort_key: u32,\n    position: vec3<f32>,\n    velocity: vec3<f32>,\n}\n",
+    );
+    assert_eq!(MyTagged::min_binding_sizes(), &[4, 16, 0, 16]);
+    assert_eq!(
+        PlanarStorageMyTagged::binding_names(),
+        &["sort_key", "position", "velocity"],
+    );
+
+    let source = PlanarStorageMyTagged::wgsl_source(0, true);
+    assert!(source.contains("var<storage, read_write> my_tagged_velocity: array<vec3<f32>>;"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyTagged>::validate_shader(&module, 0).is_empty());
+
+    assert_eq!(PlanarTextureMyLabeled::bind_group_layout_entries().len(), 1);
+    assert_eq!(
+        MyLabeled::wgsl_struct(),
+        "struct MyLabeled {\n    value: f32,\n}\n",
+    );
+}
+
+#[test]
+fn generic_fields() {
+    let packed = vec![
+        MyScalar {
+            value: 1.5f32,
+            weight: 2.0,
+        },
+        MyScalar {
+            value: 3.0,
+            weight: 4.0,
+        },
+    ];
+
+    let planar: PlanarMyScalar<f32> = Planar::from_interleaved(packed.clone());
+    assert_eq!(planar.value, vec![1.5, 3.0]);
+    assert_eq!(planar.to_interleaved(), packed);
+
+    let handle = PlanarMyScalarHandle::<f32>::default();
+    assert_eq!(handle.handle(), &Handle::default());
+
+    // type parameters are substituted through `PlanarWgslType`
+    assert_eq!(
+        MyScalar::<u32>::wgsl_struct(),
+        "struct MyScalar {\n    value: T,\n    weight: f32,\n}\n",
+    );
+    assert_eq!(MyScalar::<u32>::min_binding_sizes(), &[4, 4]);
+
+    let source = PlanarStorageMyScalar::<u32>::wgsl_source(0, true);
+    assert!(source.contains("    value: u32,\n"));
+    assert!(source.contains("var<storage, read> my_scalar_value: array<u32>;"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyScalar<u32>>::validate_shader(&module, 0).is_empty());
+
+    assert_eq!(
+        PlanarTextureMyScalar::<f32>::bind_group_layout_entries().len(),
+        2,
+    );
+
+    // every instantiation gets its own module
+    assert_eq!(
+        PlanarStorageMyScalar::<f32>::wgsl_import_path(),
+        "bevy_interleave::my_scalar_f32::storage",
+    );
+    assert_eq!(
+        PlanarStorageMyScalar::<u32>::wgsl_module_import_path(false),
+        "bevy_interleave::my_scalar_u32::storage_rw",
+    );
+    assert_eq!(
+        PackedStorageMyPackedPair::<Vec4>::wgsl_import_path(),
+        "bevy_interleave::my_packed_pair_vec4_f32::storage",
+    );
+
+    let mut composer = naga_oil::compose::Composer::default();
+    for (module, import_path) in [
+        (
+            PlanarStorageMyScalar::<f32>::wgsl_module(0, true),
+            PlanarStorageMyScalar::<f32>::wgsl_import_path(),
+        ),
+        (
+            PlanarStorageMyScalar::<u32>::wgsl_module(1, true),
+            PlanarStorageMyScalar::<u32>::wgsl_import_path(),
+        ),
+    ] {
+        composer
+            .add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
+                source: &module,
+                file_path: &import_path,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let user_shader = r#"
+        #import bevy_interleave::my_scalar_f32::storage as scalar_f32
+        #import bevy_interleave::my_scalar_u32::storage as scalar_u32
+
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+            let value = scalar_f32::get_my_scalar(id.x).value + f32(scalar_u32::get_my_scalar(id.x).value);
+        }
+    "#;
+    composer
+        .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
+            source: user_shader,
+            file_path: "user.wgsl",
+            ..Default::default()
+        })
+        .unwrap();
+
+    // type parameters are laid out once instantiated
+    assert_eq!(PackedStorageMyPackedPair::<f32>::STD430_OFFSETS, &[0, 4]);
+    assert_eq!(PackedStorageMyPackedPair::<f32>::STD430_STRIDE, 8);
+    assert_eq!(PackedStorageMyPackedPair::<Vec4>::STD430_OFFSETS, &[0, 16]);
+    assert_eq!(PackedStorageMyPackedPair::<Vec4>::STD430_STRIDE, 32);
+
+    let planar = PlanarMyPackedPair::from_interleaved(vec![MyPackedPair {
+        value: Vec4::new(1.0, 2.0, 3.0, 4.0),
+        weight: 5.0,
+    }]);
+    let bytes = PackedStorageMyPackedPair::<Vec4>::std430_bytes(&planar);
+    assert_eq!(
+        bytemuck::cast_slice::<u8, f32>(&bytes),
+        &[1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 0.0, 0.0],
+    );
+
+    let source = PackedStorageMyPackedPair::<Vec4>::wgsl_source(0, true);
+    assert!(source.contains("    value: vec4<f32>,\n"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyPackedPairPacked<Vec4>>::validate_shader(&module, 0).is_empty());
+}
+
+#[test]
+fn f16_fields() {
+    let packed = vec![MyScalar {
+        value: PlanarF16::from(1.5),
+        weight: 2.0,
+    }];
+    let planar: PlanarMyScalar<PlanarF16> = Planar::from_interleaved(packed.clone());
+    assert_eq!(f32::from(planar.value[0]), 1.5);
+    assert_eq!(planar.to_interleaved(), packed);
+
+    // `f16` instantiations enable the extension ahead of every declaration and need `SHADER_F16`
+    assert_eq!(
+        PlanarStorageMyScalar::<PlanarF16>::wgsl_features(),
+        bevy::render::settings::WgpuFeatures::SHADER_F16,
+    );
+    assert!(PlanarStorageMyScalar::<f32>::wgsl_features().is_empty());
+
+    let source = PlanarStorageMyScalar::<PlanarF16>::wgsl_source(0, true);
+    assert!(source.starts_with("enable f16;\n"));
+    assert!(source.contains("var<storage, read> my_scalar_value: array<f16>;"));
+    assert!(!PlanarStorageMyScalar::<f32>::wgsl_source(0, true).contains("enable"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::SHADER_FLOAT16,
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyScalar<PlanarF16>>::validate_shader(&module, 0).is_empty());
+
+    let module = PlanarStorageMyScalar::<PlanarF16>::wgsl_module(0, true);
+    assert!(module.starts_with("enable f16;\n\n#define_import_path bevy_interleave::my_scalar_f16::storage\n"));
+
+    let mut composer = naga_oil::compose::Composer::default()
+        .with_capabilities(naga::valid::Capabilities::SHADER_FLOAT16);
+    composer
+        .add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
+            source: &module,
+            file_path: "my_scalar_f16.wgsl",
+            ..Default::default()
+        })
+        .unwrap();
+    composer
+        .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
+            source: r#"
+                #import bevy_interleave::my_scalar_f16::storage::get_my_scalar
+
+                @compute @workgroup_size(1)
+                fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+                    let value = f32(get_my_scalar(id.x).value);
+                }
+            "#,
+            file_path: "user.wgsl",
+            ..Default::default()
+        })
+        .unwrap();
+
+    // std430 alignment of `f16` is 2, the row is padded to the `f32` weight
+    assert_eq!(PackedStorageMyPackedPair::<PlanarF16>::STD430_OFFSETS, &[0, 4]);
+    assert_eq!(PackedStorageMyPackedPair::<PlanarF16>::STD430_STRIDE, 8);
+    assert!(
+        PackedStorageMyPackedPair::<PlanarF16>::wgsl_source(0, true).starts_with("enable f16;\n")
+    );
+
+    assert_eq!(<half::f16 as PlanarWgslType>::wgsl_type(), "f16");
+}
+
+#[test]
+fn tuple_fields() {
+    let packed = vec![
+        MySample(1.5, 2, "skipped".to_string()),
+        MySample(3.0, 4, String::new()),
+    ];
+
+    // tuple fields are named by position unless renamed, skipped fields read back as their default
+    let mut planar = PlanarMySample::from_interleaved(packed);
+    assert_eq!(planar._0, vec![1.5, 3.0]);
+    assert_eq!(planar.hits, vec![2, 4]);
+    assert_eq!(planar.get(0), MySample(1.5, 2, String::new()));
+
+    Planar::set(&mut planar, 1, MySample(5.0, 6, "skipped".to_string()));
+    assert_eq!(planar.to_interleaved()[1], MySample(5.0, 6, String::new()));
+    assert_eq!(planar.subset(&[1]).hits, vec![6]);
+
+    assert_eq!(MySample::ordered_field_names(), &["_0", "hits"]);
+    assert_eq!(MySample::min_binding_sizes(), &[4, 4]);
+    assert_eq!(
+        MySample::wgsl_struct(),
+        "struct MySample {\n    _0_: f32,\n    hits: u32,\n}\n",
+    );
+
+    let source = PlanarStorageMySample::wgsl_source(0, true);
+    assert!(source.contains("var<storage, read> my_sample_hits: array<u32>;"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MySample>::validate_shader(&module, 0).is_empty());
+
+    assert_eq!(PlanarTextureMySample::bind_group_layout_entries().len(), 2);
+}
+
+#[test]
+#[allow(clippy::single_range_in_vec_init)]
+fn flattened_fields() {
+    let nested = |id: u32, value: f32| MyNested {
+        id,
+        material: MyLabeled {
+            label: format!("material {id}"),
+            value,
+        },
+        tuple: MySample(value * 2.0, id, String::new()),
+    };
+
+    // nested structs are stored as their planar structs
+    let mut planar = PlanarMyNested::from_interleaved(vec![nested(1, 0.5), nested(2, 1.5)]);
+    assert_eq!(planar.len(), 2);
+    assert_eq!(planar.id, vec![1, 2]);
+    assert_eq!(planar.material.value, vec![0.5, 1.5]);
+    assert_eq!(planar.sample.hits, vec![1, 2]);
+    assert_eq!(planar.get(1), nested(2, 1.5));
+
+    Planar::set(&mut planar, 0, nested(3, 2.5));
+    assert_eq!(planar.get(0), nested(3, 2.5));
+    assert_eq!(planar.to_interleaved(), vec![nested(3, 2.5), nested(2, 1.5)]);
+    assert_eq!(planar.subset(&[1]).to_interleaved(), vec![nested(2, 1.5)]);
+    assert_eq!(planar.material.dirty_ranges().column(1), &[0..1]);
+
+    // columns follow `ordered_field_names`, flattened columns are marked on the nested planar
+    planar.clear_dirty_ranges();
+    assert!(planar.dirty_ranges().is_empty());
+    assert!(planar.material.dirty_ranges().is_empty());
+
+    planar.mark_dirty(0, 1..2);
+    planar.mark_dirty(4, 0..2);
+    assert_eq!(planar.dirty_ranges().column(0), &[1..2]);
+    assert_eq!(planar.sample.dirty_ranges().column(1), &[0..2]);
+    assert!(planar.material.dirty_ranges().is_empty());
+
+    assert_eq!(
+        MyNested::ordered_field_names(),
+        &[
+            "id",
+            "material.label",
+            "material.value",
+            "sample._0",
+            "sample.hits",
+        ],
+    );
+    assert_eq!(MyNested::min_binding_sizes(), &[4, 0, 4, 4, 4]);
+    assert_eq!(
+        MyNested::wgsl_struct(),
+        "struct MyNested {\n    id: u32,\n}\n",
+    );
+
+    // nested bindings are appended after the struct's own bindings
+    let entries = PlanarStorageMyNested::bind_group_layout_entries(true);
+    assert_eq!(entries.len(), 4);
+    assert_eq!(
+        entries.iter().map(|entry| entry.binding).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3],
+    );
+    assert_eq!(
+        PlanarStorageMyNested::binding_names(),
+        &["id", "material.value", "sample._0", "sample.hits"],
+    );
+
+    let entries = PlanarTextureMyNested::bind_group_layout_entries();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(
+        entries.iter().map(|entry| entry.binding).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3],
+    );
+
+    let source = PlanarStorageMyNested::wgsl_source(0, true);
+    assert!(source.contains("@group(0) @binding(0) var<storage, read> my_nested_id: array<u32>;"));
+    assert!(source.contains("@group(0) @binding(4) var<uniform>"));
+
+    // nested columns are declared under this struct's names and read through their loaders
+    assert!(source.contains(
+        "@group(0) @binding(1) var<storage, read> my_nested_material_value: array<f32>;"
+    ));
+    assert!(source.contains("fn load_my_nested_sample_hits(index: u32) -> u32"));
+
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .unwrap();
+    assert!(PlanarStorageLayouts::<MyNested>::validate_shader(&module, 0).is_empty());
 }